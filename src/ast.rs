@@ -0,0 +1,147 @@
+// The tree the parser produces and the renderers walk.
+
+use crate::span::Span;
+
+/// A node in the Markdown AST, as returned by [`parse_document`](crate::parse_document),
+/// with the span of input it was parsed from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub span: Span,
+}
+
+impl Node {
+    /// A node without a source position, for building trees by hand.
+    pub fn new(kind: NodeKind) -> Self {
+        Node::spanned(kind, Span::default())
+    }
+
+    pub(crate) fn spanned(kind: NodeKind, span: Span) -> Self {
+        Node { kind, span }
+    }
+
+    // The child nodes, for the kinds that have them.
+    pub(crate) fn children_mut(&mut self) -> Option<&mut Vec<Node>> {
+        match &mut self.kind {
+            NodeKind::Document(children)
+            | NodeKind::Heading(_, children)
+            | NodeKind::Paragraph(children)
+            | NodeKind::Bold(children)
+            | NodeKind::Italic(children)
+            | NodeKind::Strikethrough(children)
+            | NodeKind::Link { children, .. }
+            | NodeKind::UnorderedList(children)
+            | NodeKind::OrderedList { children, .. }
+            | NodeKind::ListItem(children)
+            | NodeKind::Table { children, .. }
+            | NodeKind::TableRow(children)
+            | NodeKind::TableCell(children)
+            | NodeKind::FootnoteDefinition { children, .. } => Some(children),
+            NodeKind::Text(_)
+            | NodeKind::TaskMarker(_)
+            | NodeKind::CodeBlock { .. }
+            | NodeKind::Code(_)
+            | NodeKind::HtmlBlock(_)
+            | NodeKind::HtmlInline(_)
+            | NodeKind::SoftBreak
+            | NodeKind::TableOfContents
+            | NodeKind::FootnoteReference(_) => None,
+        }
+    }
+}
+
+/// What a [`Node`] is, along with its content.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeKind {
+    /// The root of the tree, holding the blocks of the document.
+    Document(Vec<Node>),
+    /// A heading with its level (1-6) and inline content.
+    Heading(usize, Vec<Node>),
+    Paragraph(Vec<Node>),
+    Bold(Vec<Node>),
+    Italic(Vec<Node>),
+    Strikethrough(Vec<Node>),
+    Text(String),
+    Link {
+        children: Vec<Node>,
+        url: String,
+        title: Option<String>,
+    },
+    UnorderedList(Vec<Node>),
+    /// A numbered list, with the number of its first item. The rest count up
+    /// from there, whatever they were written as.
+    OrderedList {
+        start: usize,
+        children: Vec<Node>,
+    },
+    ListItem(Vec<Node>),
+    /// The checkbox at the start of a task list item, and whether it is ticked.
+    TaskMarker(bool),
+    /// A fenced or indented code block, with its content as written. `info`
+    /// is what follows the opening fence, such as `rust`, and is empty for
+    /// indented code.
+    CodeBlock {
+        info: String,
+        content: String,
+    },
+    /// An inline code span.
+    Code(String),
+    HtmlBlock(String),
+    HtmlInline(String),
+    /// A line break inside a paragraph.
+    SoftBreak,
+    /// A `[[toc]]` placeholder, rendered as a table of contents of the document.
+    TableOfContents,
+    /// A table, with the alignment of each column. The children are
+    /// [`NodeKind::TableRow`]s, the first of which is the header.
+    Table {
+        alignments: Vec<Alignment>,
+        children: Vec<Node>,
+    },
+    /// A row of [`NodeKind::TableCell`]s, one for each column.
+    TableRow(Vec<Node>),
+    TableCell(Vec<Node>),
+    /// A `[^label]` reference to a footnote.
+    FootnoteReference(String),
+    /// The `[^label]: text` a footnote is defined by.
+    FootnoteDefinition {
+        label: String,
+        children: Vec<Node>,
+    },
+}
+
+/// How the cells of a table column are aligned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    /// `---`, left to the browser.
+    None,
+    /// `:--`
+    Left,
+    /// `:-:`
+    Center,
+    /// `--:`
+    Right,
+}
+
+/// A parsed Markdown document. The root is always a [`NodeKind::Document`].
+pub type Document = Node;
+
+// The text of inline nodes with all formatting dropped, as used for heading
+// slugs. Raw HTML is left out entirely.
+pub(crate) fn plain_text(nodes: &[Node]) -> String {
+    let mut text = String::new();
+    for node in nodes {
+        match &node.kind {
+            NodeKind::Text(t) | NodeKind::Code(t) => text.push_str(t),
+            NodeKind::SoftBreak => text.push(' '),
+            NodeKind::Bold(children)
+            | NodeKind::Italic(children)
+            | NodeKind::Strikethrough(children)
+            | NodeKind::Link { children, .. }
+            | NodeKind::Heading(_, children)
+            | NodeKind::Paragraph(children) => text.push_str(&plain_text(children)),
+            _ => {}
+        }
+    }
+    text
+}
@@ -0,0 +1,293 @@
+// Command-line arguments. Parsed by hand to keep the crate free of
+// dependencies; flags are long-form only, apart from `-o`, `-h` and `-V`.
+
+use mark_html::{Options, Page, Preset, SoftBreak, Style};
+
+pub(crate) const USAGE: &str = "\
+Usage: mark-html [OPTIONS] [FILE]...
+       mark-html build [OPTIONS] <SOURCE> -o <OUTPUT>
+       mark-html serve [OPTIONS] [DIR]
+       mark-html fmt [OPTIONS] [FILE]...
+
+Converts Markdown files to HTML. With no FILE, or when FILE is -, reads
+standard input. The HTML of several files is written one after another.
+
+`build` converts every .md file under the SOURCE directory to an HTML page
+under OUTPUT, copying other files, and rebuilding only what has changed.
+
+`serve` previews the Markdown files in DIR, or the current directory, at
+http://127.0.0.1:8000/, reloading pages when their file changes.
+
+`fmt` rewrites each FILE in a consistent Markdown style, or formats standard
+input to standard output. With --check, files are only checked, and the ones
+that would change are listed.
+
+Options:
+  -o, --output <FILE>      Write to FILE instead of standard output
+      --force              With build, rebuild every file
+      --clean              With build, delete output files that have no
+                           source
+      --port <PORT>        With serve, the port to listen on
+      --check              With fmt, exit with status 1 if anything would
+                           change instead of changing it
+      --wrap <COLUMNS>     With fmt, wrap paragraphs at COLUMNS
+      --fence <CHAR>       With fmt, fence code with backtick or tilde
+      --bullet <CHAR>      With fmt, start list items with dash, asterisk
+                           or plus
+      --no-align-tables    With fmt, don't pad table columns to line up
+      --strict             Report broken markup and exit with status 1
+      --standalone         Write a complete HTML page instead of a fragment
+      --plain-text         Write plain text, with the formatting taken out,
+                           instead of HTML
+      --terminal           Write text laid out for reading in a terminal,
+                           styled if standard output is one
+      --latex              Write the body of a LaTeX document instead of
+                           HTML
+      --title <TEXT>       Page title, instead of the first heading's
+      --css <URL>          Link a stylesheet from the page; can be repeated
+      --template <FILE>    Lay out the page body with FILE, filling in
+                           {{title}}, {{content}} and {{toc}}
+      --preset <NAME>      Start from a dialect: commonmark, gfm or legacy-02,
+                           which the other flags adjust
+      --tables             Enable tables
+      --strikethrough      Enable ~~strikethrough~~
+      --task-lists         Enable - [x] task list items
+      --autolinks          Enable <https://...> autolinks
+      --extended-autolinks Enable bare www. and https:// autolinks
+      --footnotes          Enable [^1] footnotes
+      --raw-html           Pass HTML in the input through unescaped
+      --filter-urls        Drop javascript: and similar link destinations
+      --heading-ids        Give headings GitHub-style ids
+      --heading-anchors    Also add a permalink to each heading
+      --source-positions   Add data-sourcepos attributes
+      --soft-breaks <HOW>  Render line breaks as newline, space or break
+      --xhtml              Write <br /> instead of <br>
+      --highlight          Highlight the code in fenced code blocks
+      --link-urls          In plain text, show link destinations
+  -h, --help               Print this help
+  -V, --version            Print the version
+";
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) enum Command {
+    #[default]
+    Convert,
+    Build,
+    Serve,
+    Format,
+}
+
+impl Command {
+    fn name(&self) -> &'static str {
+        match self {
+            Command::Convert => "file conversion",
+            Command::Build => "`build`",
+            Command::Serve => "`serve`",
+            Command::Format => "`fmt`",
+        }
+    }
+}
+
+// What the converted files are written as.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) enum OutputFormat {
+    #[default]
+    Html,
+    PlainText,
+    Terminal,
+    Latex,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Args {
+    pub(crate) command: Command,
+    pub(crate) options: Options,
+    // Empty means standard input, as does `-`.
+    pub(crate) inputs: Vec<String>,
+    pub(crate) output: Option<String>,
+    pub(crate) strict: bool,
+    pub(crate) format: OutputFormat,
+    pub(crate) force: bool,
+    pub(crate) clean: bool,
+    pub(crate) port: Option<u16>,
+    pub(crate) check: bool,
+    pub(crate) style: Style,
+    // Set by `--standalone` and the page flags, which imply it.
+    pub(crate) page: Option<Page>,
+    // The file for `Page::template`.
+    pub(crate) template: Option<String>,
+    pub(crate) help: bool,
+    pub(crate) version: bool,
+}
+
+pub(crate) fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    // The flags for `Options` so far, with their values.
+    let mut option_args: Vec<String> = Vec::new();
+    let mut args = args.into_iter().peekable();
+    match args
+        .next_if(|arg| ["build", "serve", "fmt"].contains(&arg.as_str()))
+        .as_deref()
+    {
+        Some("build") => parsed.command = Command::Build,
+        Some("serve") => parsed.command = Command::Serve,
+        Some("fmt") => parsed.command = Command::Format,
+        _ => {}
+    }
+    while let Some(arg) = args.next() {
+        if !commands(&arg).contains(&parsed.command) {
+            return Err(format!(
+                "`{}` can't be used with {}",
+                arg,
+                parsed.command.name()
+            ));
+        }
+        match arg.as_str() {
+            "-o" | "--output" => parsed.output = Some(value(&arg, args.next())?),
+            "--strict" => parsed.strict = true,
+            "--plain-text" => set_format(&mut parsed, OutputFormat::PlainText)?,
+            "--terminal" => set_format(&mut parsed, OutputFormat::Terminal)?,
+            "--latex" => set_format(&mut parsed, OutputFormat::Latex)?,
+            "--force" => parsed.force = true,
+            "--clean" => parsed.clean = true,
+            "--port" => {
+                let port = value(&arg, args.next())?;
+                parsed.port = Some(port.parse().map_err(|_| format!("bad port `{}`", port))?);
+            }
+            "--check" => parsed.check = true,
+            "--wrap" => {
+                let columns = value(&arg, args.next())?;
+                let columns = columns
+                    .parse()
+                    .map_err(|_| format!("bad column count `{}`", columns))?;
+                parsed.style = parsed.style.wrap(columns);
+            }
+            "--fence" => {
+                let fence = match value(&arg, args.next())?.as_str() {
+                    "backtick" => '`',
+                    "tilde" => '~',
+                    other => return Err(format!("unknown fence `{}`", other)),
+                };
+                parsed.style = parsed.style.fence(fence);
+            }
+            "--bullet" => {
+                let bullet = match value(&arg, args.next())?.as_str() {
+                    "dash" => '-',
+                    "asterisk" => '*',
+                    "plus" => '+',
+                    other => return Err(format!("unknown bullet `{}`", other)),
+                };
+                parsed.style = parsed.style.bullet(bullet);
+            }
+            "--no-align-tables" => parsed.style = parsed.style.align_tables(false),
+            "--standalone" => {
+                parsed.page.get_or_insert_with(Page::new);
+            }
+            "--title" => {
+                let title = value(&arg, args.next())?;
+                parsed.page = Some(parsed.page.unwrap_or_default().title(title));
+            }
+            "--css" => {
+                let url = value(&arg, args.next())?;
+                parsed.page = Some(parsed.page.unwrap_or_default().stylesheet(url));
+            }
+            "--template" => {
+                parsed.template = Some(value(&arg, args.next())?);
+                parsed.page.get_or_insert_with(Page::new);
+            }
+            "-h" | "--help" => parsed.help = true,
+            "-V" | "--version" => parsed.version = true,
+            // Everything after `--` is a file, even if it starts with `-`.
+            "--" => parsed.inputs.extend(args.by_ref()),
+            "-" => parsed.inputs.push(arg),
+            "--preset" => {
+                let preset = match value(&arg, args.next())?.as_str() {
+                    "commonmark" => Preset::CommonMark,
+                    "gfm" => Preset::Gfm,
+                    "legacy-02" => Preset::Legacy02,
+                    other => return Err(format!("unknown preset `{}`", other)),
+                };
+                // The preset comes first wherever it is given, so the flags
+                // before it are applied again on top of it.
+                parsed.options = Options::preset(preset);
+                let mut earlier = option_args.iter().cloned();
+                while let Some(flag) = earlier.next() {
+                    option_flag(&mut parsed.options, &flag, &mut earlier)?;
+                }
+            }
+            _ if arg.starts_with('-') => {
+                let mut taken = vec![arg.clone()];
+                let mut recorded = args.by_ref().inspect(|value| taken.push(value.clone()));
+                if !option_flag(&mut parsed.options, &arg, &mut recorded)? {
+                    return Err(format!("unknown option `{}`", arg));
+                }
+                option_args.extend(taken);
+            }
+            _ => parsed.inputs.push(arg),
+        }
+    }
+    Ok(parsed)
+}
+
+// Applies `flag` to `options` if it is one of the flags for them, taking its
+// value from `args` if it has one. Returns whether it was.
+pub(crate) fn option_flag(
+    options: &mut Options,
+    flag: &str,
+    args: &mut impl Iterator<Item = String>,
+) -> Result<bool, String> {
+    match flag {
+        "--soft-breaks" => {
+            options.soft_breaks = match value(flag, args.next())?.as_str() {
+                "newline" => SoftBreak::Newline,
+                "space" => SoftBreak::Space,
+                "break" => SoftBreak::Break,
+                other => return Err(format!("unknown soft break style `{}`", other)),
+            }
+        }
+        "--tables" => options.tables = true,
+        "--strikethrough" => options.strikethrough = true,
+        "--task-lists" => options.task_lists = true,
+        "--autolinks" => options.autolinks = true,
+        "--extended-autolinks" => options.extended_autolinks = true,
+        "--footnotes" => options.footnotes = true,
+        "--raw-html" => options.raw_html = true,
+        "--filter-urls" => options.filter_urls = true,
+        "--heading-ids" => options.heading_ids = true,
+        "--heading-anchors" => options.heading_anchors = true,
+        "--source-positions" => options.source_positions = true,
+        "--xhtml" => options.xhtml = true,
+        "--highlight" => options.syntax_highlighting = true,
+        "--link-urls" => options.link_urls = true,
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn value(flag: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("`{}` needs a value", flag))
+}
+
+// The commands `arg` is a flag of. Flags for `Options`, and anything that
+// isn't a flag, are for all of them.
+fn commands(arg: &str) -> &'static [Command] {
+    use Command::*;
+    match arg {
+        "-o" | "--output" => &[Convert, Build, Format],
+        "--strict" => &[Convert, Build],
+        "--plain-text" | "--terminal" | "--latex" => &[Convert],
+        "--force" | "--clean" => &[Build],
+        "--port" => &[Serve],
+        "--check" | "--wrap" | "--fence" | "--bullet" | "--no-align-tables" => &[Format],
+        "--standalone" | "--title" | "--css" | "--template" => &[Convert, Build, Serve],
+        _ => &[Convert, Build, Serve, Format],
+    }
+}
+
+fn set_format(args: &mut Args, format: OutputFormat) -> Result<(), String> {
+    if args.format != OutputFormat::Html && args.format != format {
+        return Err("only one of --plain-text, --terminal and --latex can be given".to_string());
+    }
+    args.format = format;
+    Ok(())
+}
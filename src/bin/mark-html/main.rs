@@ -0,0 +1,214 @@
+// The `mark-html` command: converts Markdown files, or standard input, to HTML,
+// and builds, serves and formats them.
+
+#[cfg(test)]
+mod test;
+
+mod args;
+mod serve;
+
+use args::{Args, Command, OutputFormat, USAGE, parse_args};
+use mark_html::{Page, ParseError, Site, Terminal};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("mark-html: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    if args.help {
+        print!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    if args.version {
+        println!("mark-html {}", env!("CARGO_PKG_VERSION"));
+        return ExitCode::SUCCESS;
+    }
+    let result = match args.command {
+        Command::Convert => convert(&args),
+        Command::Build => build(&args),
+        Command::Serve => serve::serve(&args),
+        Command::Format => format(&args),
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(message) => {
+            eprintln!("mark-html: {}", message);
+            ExitCode::from(2)
+        }
+    }
+}
+
+// Converts every input and writes the HTML out. Returns whether all of them
+// were free of problems, which only `--strict` checks for.
+fn convert(args: &Args) -> Result<bool, String> {
+    let stdin = ["-".to_string()];
+    let inputs = if args.inputs.is_empty() {
+        &stdin[..]
+    } else {
+        &args.inputs[..]
+    };
+    let page = page(args)?;
+    if args.format != OutputFormat::Html && page.is_some() {
+        return Err("page flags are only for HTML output".to_string());
+    }
+    let mut html = String::new();
+    let mut clean = true;
+    for input in inputs {
+        let markdown = read_input(input)?;
+        let name = if input == "-" { "<stdin>" } else { input };
+        match render(&markdown, args, page.as_ref()) {
+            Ok(rendered) => {
+                html.push_str(&rendered);
+                if !html.ends_with('\n') {
+                    html.push('\n');
+                }
+            }
+            Err(error) => {
+                for diagnostic in &error.diagnostics {
+                    eprintln!("{}:{}", name, diagnostic);
+                }
+                clean = false;
+            }
+        }
+    }
+    match &args.output {
+        Some(path) => fs::write(path, html).map_err(|error| format!("{}: {}", path, error))?,
+        None => io::stdout()
+            .write_all(html.as_bytes())
+            .map_err(|error| error.to_string())?,
+    }
+    Ok(clean)
+}
+
+// Builds a site from the source directory. Returns whether every page was
+// free of problems, which only `--strict` checks for.
+fn build(args: &Args) -> Result<bool, String> {
+    let [source] = &args.inputs[..] else {
+        return Err("build needs one source directory".to_string());
+    };
+    let Some(output) = &args.output else {
+        return Err("build needs an output directory, given with -o".to_string());
+    };
+    let report = Site::new(source, output)
+        .options(args.options.clone())
+        .page(page(args)?.unwrap_or_default())
+        .force(args.force)
+        .clean(args.clean)
+        .build()
+        .map_err(|error| error.to_string())?;
+    for path in &report.stale {
+        let path = Path::new(output).join(path);
+        match args.clean {
+            true => eprintln!("removed {}", path.display()),
+            false => eprintln!("{}: has no source; --clean removes it", path.display()),
+        }
+    }
+    for (path, diagnostics) in &report.diagnostics {
+        for diagnostic in diagnostics {
+            eprintln!("{}:{}", Path::new(source).join(path).display(), diagnostic);
+        }
+    }
+    eprintln!(
+        "built {} pages, copied {} files, {} unchanged",
+        report.converted.len(),
+        report.copied.len(),
+        report.unchanged
+    );
+    Ok(!args.strict || report.diagnostics.is_empty())
+}
+
+// Formats every input file in place, or standard input to standard output.
+// With `--check` nothing is written, and the result is whether every input
+// was formatted already.
+fn format(args: &Args) -> Result<bool, String> {
+    let stdin = ["-".to_string()];
+    let inputs = if args.inputs.is_empty() {
+        &stdin[..]
+    } else {
+        &args.inputs[..]
+    };
+    if args.output.is_some() && inputs != stdin {
+        return Err("fmt rewrites files in place; -o is for standard input".to_string());
+    }
+    let mut clean = true;
+    for input in inputs {
+        let markdown = read_input(input)?;
+        let formatted = mark_html::format_markdown(&markdown, &args.options, &args.style);
+        let name = if input == "-" { "<stdin>" } else { input };
+        if args.check {
+            if formatted != markdown {
+                eprintln!("{}: not formatted", name);
+                clean = false;
+            }
+        } else if input == "-" {
+            match &args.output {
+                Some(path) => {
+                    fs::write(path, formatted).map_err(|error| format!("{}: {}", path, error))?
+                }
+                None => io::stdout()
+                    .write_all(formatted.as_bytes())
+                    .map_err(|error| error.to_string())?,
+            }
+        } else if formatted != markdown {
+            fs::write(input, formatted).map_err(|error| format!("{}: {}", input, error))?;
+        }
+    }
+    Ok(clean)
+}
+
+// The page settings, with the `--template` file read in.
+fn page(args: &Args) -> Result<Option<Page>, String> {
+    match (&args.page, &args.template) {
+        (Some(page), Some(path)) => {
+            let template =
+                fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+            Ok(Some(page.clone().template(template)))
+        }
+        (page, _) => Ok(page.clone()),
+    }
+}
+
+fn render(markdown: &str, args: &Args, page: Option<&Page>) -> Result<String, ParseError> {
+    if args.strict {
+        mark_html::try_to_html(markdown, &args.options)?;
+    }
+    Ok(match (&args.format, page) {
+        (_, Some(page)) => mark_html::to_html_page(markdown, &args.options, page),
+        (OutputFormat::Html, None) => mark_html::to_html_with_options(markdown, &args.options),
+        (OutputFormat::PlainText, None) => {
+            mark_html::to_plain_text_with_options(markdown, &args.options)
+        }
+        (OutputFormat::Terminal, None) => {
+            // Escape codes are only for a terminal, not for a file.
+            let terminal = match args.output {
+                Some(_) => Terminal::detect().styled(false),
+                None => Terminal::detect(),
+            };
+            let document = mark_html::parse_document_with_options(markdown, &args.options);
+            mark_html::render_terminal(&document, &terminal)
+        }
+        (OutputFormat::Latex, None) => {
+            let document = mark_html::parse_document_with_options(markdown, &args.options);
+            mark_html::render_latex(&document)
+        }
+    })
+}
+
+fn read_input(input: &str) -> Result<String, String> {
+    if input == "-" {
+        let mut markdown = String::new();
+        io::stdin()
+            .read_to_string(&mut markdown)
+            .map_err(|error| format!("<stdin>: {}", error))?;
+        return Ok(markdown);
+    }
+    fs::read_to_string(input).map_err(|error| format!("{}: {}", input, error))
+}
@@ -0,0 +1,273 @@
+// `mark-html serve`: a small HTTP server for previewing a directory of
+// Markdown while editing it. Pages are rendered on every request, and a
+// script in each one polls the file's modification time and reloads the page
+// when it changes. Only for local use: it listens on 127.0.0.1 alone.
+
+use crate::args::Args;
+use mark_html::{escape_attribute, escape_html};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::thread;
+use std::time::UNIX_EPOCH;
+
+// Where pages ask for the modification time of their file, followed by the
+// page's own path.
+const MTIME_PREFIX: &str = "/__mark-html/mtime";
+
+const RELOAD_SCRIPT: &str = "<script>
+(function () {
+  var last = null;
+  setInterval(function () {
+    fetch(\"/__mark-html/mtime\" + location.pathname, { cache: \"no-store\" })
+      .then(function (response) { return response.text(); })
+      .then(function (mtime) {
+        if (last !== null && mtime !== last) location.reload();
+        last = mtime;
+      })
+      .catch(function () {});
+  }, 1000);
+})();
+</script>";
+
+// Serves the directory given as the only input, or the current one. Only
+// returns if the server can't start.
+pub(crate) fn serve(args: &Args) -> Result<bool, String> {
+    let root = match &args.inputs[..] {
+        [] => PathBuf::from("."),
+        [root] => PathBuf::from(root),
+        _ => return Err("serve takes one directory".to_string()),
+    };
+    if !root.is_dir() {
+        return Err(format!("{}: not a directory", root.display()));
+    }
+    let address = format!("127.0.0.1:{}", args.port.unwrap_or(8000));
+    let listener =
+        TcpListener::bind(&address).map_err(|error| format!("{}: {}", address, error))?;
+    eprintln!("serving {} at http://{}/", root.display(), address);
+    thread::scope(|scope| {
+        for stream in listener.incoming().flatten() {
+            let root = &root;
+            scope.spawn(move || {
+                if let Err(error) = handle(stream, root, args) {
+                    eprintln!("mark-html: {}", error);
+                }
+            });
+        }
+    });
+    Ok(true)
+}
+
+fn handle(mut stream: TcpStream, root: &Path, args: &Args) -> io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers don't matter, but have to be read before replying.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return respond(&mut stream, "400 Bad Request", "text/plain", b"bad request");
+    };
+    if method != "GET" {
+        return respond(&mut stream, "405 Method Not Allowed", "text/plain", b"");
+    }
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+
+    if let Some(page) = path.strip_prefix(MTIME_PREFIX) {
+        let mtime = resolve(root, page)
+            .and_then(|file| modified(&file))
+            .unwrap_or_default();
+        return respond(&mut stream, "200 OK", "text/plain", mtime.as_bytes());
+    }
+    // Relative links in a directory's index page only work from a URL that
+    // ends in `/`.
+    let is_dir = percent_decode(path)
+        .is_some_and(|decoded| root.join(decoded.trim_start_matches('/')).is_dir());
+    if is_dir && !path.ends_with('/') {
+        return write!(
+            stream,
+            "HTTP/1.1 301 Moved Permanently\r\nLocation: {}/\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            path
+        );
+    }
+    let Some(file) = resolve(root, path) else {
+        return respond(&mut stream, "404 Not Found", "text/plain", b"not found");
+    };
+    if file.is_dir() {
+        let listing = list_directory(&file, path)?;
+        return respond(
+            &mut stream,
+            "200 OK",
+            "text/html; charset=utf-8",
+            listing.as_bytes(),
+        );
+    }
+    if file.extension().is_some_and(|extension| extension == "md") {
+        let markdown = fs::read_to_string(&file)?;
+        let page = crate::page(args)
+            .map_err(io::Error::other)?
+            .unwrap_or_default();
+        let html = inject_reload(&mark_html::to_html_page(&markdown, &args.options, &page));
+        return respond(
+            &mut stream,
+            "200 OK",
+            "text/html; charset=utf-8",
+            html.as_bytes(),
+        );
+    }
+    respond(
+        &mut stream,
+        "200 OK",
+        content_type(&file),
+        &fs::read(&file)?,
+    )
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+// The file under `root` that a URL path refers to, if it exists. A directory
+// stands for its `index.md` or `README.md` if it has one, and a `.html` path
+// for the `.md` file it would be built from. Paths leaving `root` are
+// refused, including through symbolic links.
+pub(crate) fn resolve(root: &Path, url_path: &str) -> Option<PathBuf> {
+    let decoded = percent_decode(url_path)?;
+    let relative = Path::new(decoded.trim_start_matches('/'));
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        return None;
+    }
+    let path = root.join(relative);
+    let file = if path.is_dir() {
+        ["index.md", "README.md"]
+            .iter()
+            .map(|index| path.join(index))
+            .find(|index| index.is_file())
+            .or(Some(path))
+    } else if path.is_file() {
+        Some(path)
+    } else {
+        let markdown = path.with_extension("md");
+        (path
+            .extension()
+            .is_some_and(|extension| extension == "html")
+            && markdown.is_file())
+        .then_some(markdown)
+    };
+    file.filter(|file| within(root, file))
+}
+
+// Whether `path` is under `root` once symbolic links are followed.
+fn within(root: &Path, path: &Path) -> bool {
+    match (root.canonicalize(), path.canonicalize()) {
+        (Ok(root), Ok(path)) => path.starts_with(root),
+        _ => false,
+    }
+}
+
+// Decodes `%XX` escapes. `None` for malformed escapes or invalid UTF-8.
+pub(crate) fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+// Adds the reload script at the end of the page's body.
+pub(crate) fn inject_reload(html: &str) -> String {
+    match html.rfind("</body>") {
+        Some(end) => format!("{}{}\n{}", &html[..end], RELOAD_SCRIPT, &html[end..]),
+        None => format!("{}{}\n", html, RELOAD_SCRIPT),
+    }
+}
+
+fn modified(path: &Path) -> Option<String> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    let since_epoch = modified.duration_since(UNIX_EPOCH).ok()?;
+    Some(since_epoch.as_nanos().to_string())
+}
+
+pub(crate) fn content_type(path: &Path) -> &'static str {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    match extension.unwrap_or_default().to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+// A page linking to the Markdown files and directories in `dir`.
+fn list_directory(dir: &Path, url_path: &str) -> io::Result<String> {
+    let mut names = fs::read_dir(dir)?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let is_dir = entry.path().is_dir();
+            let shown = !name.starts_with('.') && (is_dir || name.ends_with(".md"));
+            shown.then(|| if is_dir { name + "/" } else { name })
+        })
+        .collect::<Vec<String>>();
+    names.sort();
+    let base = if url_path.ends_with('/') {
+        url_path.to_string()
+    } else {
+        format!("{}/", url_path)
+    };
+    let items = names
+        .iter()
+        .map(|name| {
+            format!(
+                "<li><a href=\"{}{}\">{}</a></li>",
+                escape_attribute(&base),
+                escape_attribute(name),
+                escape_html(name)
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    Ok(format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n<ul>\n{}\n</ul>\n</body>\n</html>\n",
+        escape_html(&base),
+        items
+    ))
+}
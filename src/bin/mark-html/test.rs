@@ -0,0 +1,239 @@
+use crate::args::*;
+use crate::serve::{content_type, inject_reload, percent_decode, resolve};
+use mark_html::{Options, Page, Preset, SoftBreak, Style};
+use std::path::Path;
+
+fn args(args: &[&str]) -> Result<Args, String> {
+    parse_args(args.iter().map(|arg| arg.to_string()))
+}
+
+#[test]
+fn test_parse_args() {
+    assert_eq!(args(&[]), Ok(Args::default()));
+    assert_eq!(
+        args(&["a.md", "-o", "out.html", "--strict", "-", "--", "--b.md"]),
+        Ok(Args {
+            inputs: vec!["a.md".to_string(), "-".to_string(), "--b.md".to_string()],
+            output: Some("out.html".to_string()),
+            strict: true,
+            ..Args::default()
+        })
+    );
+    assert_eq!(
+        args(&["--nope"]),
+        Err("unknown option `--nope`".to_string())
+    );
+    assert_eq!(args(&["a.md", "-o"]), Err("`-o` needs a value".to_string()));
+}
+
+#[test]
+fn test_parse_args_options() {
+    let parsed = args(&["--preset", "gfm", "--heading-ids", "--soft-breaks", "space"]).unwrap();
+    assert_eq!(
+        parsed.options,
+        Options::preset(Preset::Gfm)
+            .heading_ids(true)
+            .soft_breaks(SoftBreak::Space)
+    );
+    let parsed = args(&["--tables", "--footnotes", "--raw-html"]).unwrap();
+    assert_eq!(
+        parsed.options,
+        Options::new().tables(true).footnotes(true).raw_html(true)
+    );
+    let parsed = args(&["--plain-text", "--link-urls"]).unwrap();
+    assert_eq!(parsed.format, OutputFormat::PlainText);
+    assert_eq!(parsed.options, Options::new().link_urls(true));
+    assert_eq!(
+        args(&["--terminal"]).unwrap().format,
+        OutputFormat::Terminal
+    );
+    assert_eq!(args(&["--latex"]).unwrap().format, OutputFormat::Latex);
+    assert_eq!(
+        args(&["--plain-text", "--latex"]),
+        Err("only one of --plain-text, --terminal and --latex can be given".to_string())
+    );
+    // Flags before the preset aren't lost to it.
+    let parsed = args(&[
+        "--raw-html",
+        "--soft-breaks",
+        "space",
+        "--preset",
+        "gfm",
+        "--xhtml",
+    ]);
+    assert_eq!(
+        parsed.unwrap().options,
+        Options::preset(Preset::Gfm)
+            .raw_html(true)
+            .soft_breaks(SoftBreak::Space)
+            .xhtml(true)
+    );
+    assert_eq!(
+        args(&["--preset", "markdown"]),
+        Err("unknown preset `markdown`".to_string())
+    );
+}
+
+#[test]
+fn test_parse_args_page() {
+    assert_eq!(args(&["a.md"]).unwrap().page, None);
+    assert_eq!(args(&["--standalone"]).unwrap().page, Some(Page::new()));
+    let parsed = args(&[
+        "--css",
+        "a.css",
+        "--title",
+        "T",
+        "--css",
+        "b.css",
+        "--template",
+        "t.html",
+    ])
+    .unwrap();
+    assert_eq!(
+        parsed.page,
+        Some(
+            Page::new()
+                .stylesheet("a.css")
+                .title("T")
+                .stylesheet("b.css")
+        )
+    );
+    assert_eq!(parsed.template, Some("t.html".to_string()));
+}
+
+#[test]
+fn test_parse_args_build() {
+    assert_eq!(
+        args(&["build", "docs", "-o", "public", "--force", "--clean"]),
+        Ok(Args {
+            command: Command::Build,
+            inputs: vec!["docs".to_string()],
+            output: Some("public".to_string()),
+            force: true,
+            clean: true,
+            ..Args::default()
+        })
+    );
+    // Only as the first argument is it a subcommand.
+    assert_eq!(args(&["a.md", "build"]).unwrap().command, Command::Convert);
+    assert_eq!(
+        args(&["serve", "--force"]),
+        Err("`--force` can't be used with `serve`".to_string())
+    );
+    assert_eq!(
+        args(&["a.md", "--clean"]),
+        Err("`--clean` can't be used with file conversion".to_string())
+    );
+    assert_eq!(
+        args(&["fmt", "--css", "a.css"]),
+        Err("`--css` can't be used with `fmt`".to_string())
+    );
+    assert_eq!(
+        args(&["build", "--latex"]),
+        Err("`--latex` can't be used with `build`".to_string())
+    );
+    // Flag values and files after `--` aren't flags.
+    let parsed = args(&["build", "--title", "--port", "--", "--check"]).unwrap();
+    assert_eq!(parsed.inputs, vec!["--check".to_string()]);
+}
+
+#[test]
+fn test_parse_args_serve() {
+    let parsed = args(&["serve", "docs", "--port", "9000", "--preset", "gfm"]).unwrap();
+    assert_eq!(parsed.command, Command::Serve);
+    assert_eq!(parsed.inputs, vec!["docs".to_string()]);
+    assert_eq!(parsed.port, Some(9000));
+    assert_eq!(
+        args(&["serve", "--port", "x"]),
+        Err("bad port `x`".to_string())
+    );
+}
+
+#[test]
+fn test_parse_args_fmt() {
+    assert_eq!(
+        args(&[
+            "fmt",
+            "--check",
+            "--wrap",
+            "80",
+            "--fence",
+            "tilde",
+            "README.md"
+        ]),
+        Ok(Args {
+            command: Command::Format,
+            inputs: vec!["README.md".to_string()],
+            check: true,
+            style: Style::new().wrap(80).fence('~'),
+            ..Args::default()
+        })
+    );
+    let parsed = args(&[
+        "fmt",
+        "--no-align-tables",
+        "--bullet",
+        "plus",
+        "--preset",
+        "gfm",
+    ])
+    .unwrap();
+    assert_eq!(parsed.style, Style::new().align_tables(false).bullet('+'));
+    assert_eq!(parsed.options, Options::preset(Preset::Gfm));
+    assert_eq!(
+        args(&["fmt", "--fence", "dash"]),
+        Err("unknown fence `dash`".to_string())
+    );
+    assert_eq!(
+        args(&["fmt", "--bullet", "*"]),
+        Err("unknown bullet `*`".to_string())
+    );
+}
+
+#[test]
+fn test_serve_resolve() {
+    let root = std::env::temp_dir().join(format!("mark-html-serve-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("a dir")).unwrap();
+    std::fs::create_dir_all(root.join("empty")).unwrap();
+    std::fs::write(root.join("a dir/index.md"), "").unwrap();
+    std::fs::write(root.join("page.md"), "").unwrap();
+
+    assert_eq!(
+        resolve(&root, "/a%20dir/"),
+        Some(root.join("a dir/index.md"))
+    );
+    assert_eq!(resolve(&root, "/empty/"), Some(root.join("empty/")));
+    assert_eq!(resolve(&root, "/page.md"), Some(root.join("page.md")));
+    assert_eq!(resolve(&root, "/page.html"), Some(root.join("page.md")));
+    assert_eq!(resolve(&root, "/missing.html"), None);
+    assert_eq!(resolve(&root, "/a%20dir/../page.md"), None);
+    assert_eq!(resolve(&root, "/%2e%2e/etc/passwd"), None);
+    assert_eq!(resolve(&root, "/bad%2"), None);
+    #[cfg(unix)]
+    {
+        let outside = root.with_extension("outside");
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("secret.md"), "").unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("out")).unwrap();
+        std::os::unix::fs::symlink(root.join("page.md"), root.join("alias.md")).unwrap();
+        assert_eq!(resolve(&root, "/out/secret.md"), None);
+        assert_eq!(resolve(&root, "/out/"), None);
+        assert_eq!(resolve(&root, "/alias.md"), Some(root.join("alias.md")));
+        std::fs::remove_dir_all(outside).unwrap();
+    }
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_serve_helpers() {
+    assert_eq!(percent_decode("a%20b%C3%A9"), Some("a bé".to_string()));
+    assert_eq!(percent_decode("%zz"), None);
+    let html = inject_reload("<body>\n<p>x</p>\n</body>\n</html>\n");
+    assert!(html.starts_with("<body>\n<p>x</p>\n<script>"));
+    assert!(html.ends_with("</script>\n</body>\n</html>\n"));
+    assert_eq!(
+        content_type(Path::new("a/b.CSS")),
+        "text/css; charset=utf-8"
+    );
+    assert_eq!(content_type(Path::new("a/b")), "application/octet-stream");
+}
@@ -0,0 +1,92 @@
+// Problems found in the input. Parsing never fails; malformed markup is still
+// rendered as best it can be, but these say where the result is probably not
+// what the author meant.
+
+use crate::span::Span;
+use std::fmt;
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The markup swallows part of the document, such as a code fence that
+    /// is never closed.
+    Error,
+    /// The markup shows up as literal text instead of what was intended.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => f.write_str("error"),
+            Severity::Warning => f.write_str("warning"),
+        }
+    }
+}
+
+/// A problem found while parsing, as returned by [`parse_with_diagnostics`](crate::parse_with_diagnostics).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The part of the input the problem is about.
+    pub span: Span,
+    pub message: String,
+    /// A stable identifier for the kind of problem, such as
+    /// `"unclosed-emphasis"`, for filtering.
+    pub code: &'static str,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(severity: Severity, span: Span, code: &'static str, message: String) -> Self {
+        Diagnostic {
+            severity,
+            span,
+            message,
+            code,
+        }
+    }
+}
+
+// Formats as `line:column: severity: message [code]`, the way compilers do.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {} [{}]",
+            self.span.start.line, self.span.start.column, self.severity, self.message, self.code
+        )
+    }
+}
+
+/// The error returned by [`try_to_html`](crate::try_to_html) for input with
+/// broken markup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// Where the first problem starts, counting from 1.
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    /// Every problem found, starting with the one described above.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ParseError {
+    // `None` if there are no diagnostics, so nothing to fail on.
+    pub(crate) fn from_diagnostics(diagnostics: Vec<Diagnostic>) -> Option<Self> {
+        let first = diagnostics.first()?;
+        Some(ParseError {
+            line: first.span.start.line,
+            column: first.span.start.column,
+            message: first.message.clone(),
+            diagnostics,
+        })
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
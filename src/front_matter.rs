@@ -0,0 +1,204 @@
+// Front matter: a block of metadata at the very top of a document, delimited
+// by `---` lines (YAML style, `key: value`) or `+++` lines (TOML style,
+// `key = value`). Only the simple subset blog posts and docs pages use is
+// understood: scalars, lists and, for TOML, `[table]` sections.
+
+use std::collections::BTreeMap;
+
+/// A value in a document's front matter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    List(Vec<Value>),
+}
+
+impl Value {
+    /// The value as a string slice, if it is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// The keys and values found in a document's front matter. Keys from a TOML
+/// `[table]` section are prefixed with the table name, as in `author.name`.
+pub type Metadata = BTreeMap<String, Value>;
+
+// Splits front matter off the start of `input`, returning its values and the
+// rest of the document. A block without a closing delimiter isn't front matter.
+pub(crate) fn split_front_matter(input: &str) -> Option<(Metadata, &str)> {
+    let (first_line, mut rest) = input.split_once('\n')?;
+    let yaml = match first_line.trim_end() {
+        "---" => true,
+        "+++" => false,
+        _ => return None,
+    };
+
+    let mut lines = Vec::new();
+    loop {
+        let (line, after, last) = match rest.split_once('\n') {
+            Some((line, after)) => (line, after, false),
+            None => (rest, "", true),
+        };
+        let closing = match line.trim_end() {
+            "---" | "..." => yaml,
+            "+++" => !yaml,
+            _ => false,
+        };
+        if closing {
+            let metadata = if yaml {
+                parse_yaml(&lines)
+            } else {
+                parse_toml(&lines)
+            };
+            return Some((metadata, after));
+        }
+        if last {
+            return None;
+        }
+        lines.push(line);
+        rest = after;
+    }
+}
+
+fn parse_yaml(lines: &[&str]) -> Metadata {
+    let mut metadata = Metadata::new();
+    // The key of a `key:` line with no value, which `- item` lines add to.
+    let mut list_key: Option<String> = None;
+    for line in lines {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let (Some(item), Some(key)) = (trimmed.strip_prefix("- "), &list_key) {
+            if let Some(Value::List(items)) = metadata.get_mut(key) {
+                items.push(parse_value(item));
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let key = unquote(key.trim()).to_string();
+        if value.trim().is_empty() {
+            metadata.insert(key.clone(), Value::List(Vec::new()));
+            list_key = Some(key);
+        } else {
+            metadata.insert(key, parse_value(value));
+            list_key = None;
+        }
+    }
+    metadata
+}
+
+fn parse_toml(lines: &[&str]) -> Metadata {
+    let mut metadata = Metadata::new();
+    let mut table = String::new();
+    for line in lines {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+            table = format!("{}.", name.trim());
+            continue;
+        }
+        if let Some((key, value)) = trimmed.split_once('=') {
+            let key = format!("{}{}", table, unquote(key.trim()));
+            metadata.insert(key, parse_value(value));
+        }
+    }
+    metadata
+}
+
+// Parses a scalar or an inline `[a, b]` list. Anything that isn't a quoted
+// string, boolean, number or list is taken as a plain string.
+fn parse_value(raw: &str) -> Value {
+    let raw = strip_comment(raw.trim());
+    if let Some(inner) = raw.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+        return Value::List(
+            split_list(inner)
+                .into_iter()
+                .filter(|item| !item.trim().is_empty())
+                .map(parse_value)
+                .collect(),
+        );
+    }
+    if is_quoted(raw) {
+        return Value::String(unquote(raw).replace("\\\"", "\""));
+    }
+    match raw {
+        "true" => return Value::Boolean(true),
+        "false" => return Value::Boolean(false),
+        _ => {}
+    }
+    if let Ok(n) = raw.replace('_', "").parse::<i64>() {
+        return Value::Integer(n);
+    }
+    let numeric = raw
+        .chars()
+        .all(|c| c.is_ascii_digit() || "+-.eE_".contains(c));
+    if numeric && let Ok(n) = raw.replace('_', "").parse::<f64>() {
+        return Value::Float(n);
+    }
+    Value::String(raw.to_string())
+}
+
+fn is_quoted(raw: &str) -> bool {
+    raw.len() >= 2
+        && ((raw.starts_with('"') && raw.ends_with('"'))
+            || (raw.starts_with('\'') && raw.ends_with('\'')))
+}
+
+fn unquote(raw: &str) -> &str {
+    if is_quoted(raw) {
+        &raw[1..raw.len() - 1]
+    } else {
+        raw
+    }
+}
+
+// Drops a trailing ` # comment`, unless the `#` is inside quotes.
+fn strip_comment(raw: &str) -> &str {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, c) in raw.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '#') if prev.is_whitespace() => return raw[..i].trim_end(),
+            _ => {}
+        }
+        prev = c;
+    }
+    raw
+}
+
+// Splits the inside of an inline list on commas outside of quotes and
+// nested brackets.
+fn split_list(inner: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut quote = None;
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '[') => depth += 1,
+            (None, ']') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                items.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&inner[start..]);
+    items
+}
@@ -0,0 +1,556 @@
+// Syntax highlighting for code blocks, done while rendering so the output
+// needs no JavaScript. Each language is described by a `Syntax` table driving
+// one generic scanner, which knows just enough (comments, strings, numbers,
+// keywords, keys) to color code the way editors do, not to parse it.
+
+use crate::html::escape_html;
+
+/// Turns the content of a code block into highlighted HTML, for
+/// [`to_html_with_highlighter`](crate::to_html_with_highlighter).
+///
+/// To add a language, handle it and hand the rest to [`BuiltinHighlighter`]:
+///
+/// ```
+/// use mark_html::{BuiltinHighlighter, Highlighter};
+///
+/// struct WithSql;
+///
+/// impl Highlighter for WithSql {
+///     fn highlight(&self, language: &str, code: &str) -> Option<String> {
+///         match language {
+///             "sql" => Some(format!("<span class=\"tok-string\">{}</span>", code)),
+///             _ => BuiltinHighlighter.highlight(language, code),
+///         }
+///     }
+/// }
+/// ```
+pub trait Highlighter {
+    /// The HTML for `code`, written in `language`, which is the first word of
+    /// the code block's info string. The HTML goes inside `<code>`, so text
+    /// in it must be escaped. `None` if the language isn't supported, in
+    /// which case the code is rendered as usual.
+    fn highlight(&self, language: &str, code: &str) -> Option<String>;
+}
+
+/// The highlighter used for [`Options::syntax_highlighting`](crate::Options::syntax_highlighting).
+/// It knows Rust, Python, JavaScript, TypeScript, shell, JSON, TOML, YAML and
+/// diffs, and wraps tokens in `<span class="tok-keyword">` and the like,
+/// which [`HIGHLIGHT_CSS`] gives colors to.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BuiltinHighlighter;
+
+impl Highlighter for BuiltinHighlighter {
+    fn highlight(&self, language: &str, code: &str) -> Option<String> {
+        let html = highlight_tokens(language, code)?
+            .into_iter()
+            .map(|(class, text)| match class {
+                Some(class) => {
+                    format!("<span class=\"tok-{}\">{}</span>", class, escape_html(text))
+                }
+                None => escape_html(text),
+            })
+            .collect();
+        Some(html)
+    }
+}
+
+// `code` split into highlighted pieces, or `None` if the language isn't
+// supported. The terminal renderer colors these itself.
+pub(crate) fn highlight_tokens<'a>(
+    language: &str,
+    code: &'a str,
+) -> Option<Vec<HighlightToken<'a>>> {
+    match language.to_ascii_lowercase().as_str() {
+        "diff" | "patch" => Some(highlight_diff(code)),
+        language => Some(highlight_with(syntax(language)?, code)),
+    }
+}
+
+/// A stylesheet for the classes [`BuiltinHighlighter`] uses: `tok-keyword`,
+/// `tok-type`, `tok-function`, `tok-string`, `tok-number`, `tok-literal`,
+/// `tok-comment`, `tok-variable`, `tok-key`, and `tok-inserted`,
+/// `tok-deleted` and `tok-meta` for diffs.
+pub const HIGHLIGHT_CSS: &str = "\
+.tok-keyword { color: #a626a4; }
+.tok-type { color: #c18401; }
+.tok-function { color: #4078f2; }
+.tok-string { color: #50a14f; }
+.tok-number, .tok-literal { color: #986801; }
+.tok-comment { color: #a0a1a7; font-style: italic; }
+.tok-variable, .tok-key { color: #e45649; }
+.tok-inserted { color: #22863a; background-color: #f0fff4; }
+.tok-deleted { color: #b31d28; background-color: #ffeef0; }
+.tok-meta { color: #6f42c1; }
+";
+
+// What the scanner needs to know about a language.
+#[derive(Default)]
+struct Syntax {
+    keywords: &'static [&'static str],
+    types: &'static [&'static str],
+    literals: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    // Python's `"""` and `'''` strings.
+    triple_quotes: bool,
+    // Backslash escapes don't work in `'...'` strings, as in shell and TOML.
+    raw_single_quotes: bool,
+    // Words starting with a capital letter are types, as in Rust.
+    capitalized_types: bool,
+    // A word followed by `(` (or, in Rust, `!`) is a function call.
+    calls: bool,
+    // Rust's `'a` lifetimes, which aren't the start of a string.
+    lifetimes: bool,
+    // Shell `$NAME` and `${NAME}` variables.
+    variables: bool,
+    keys: Keys,
+}
+
+// How keys in configuration formats look.
+#[derive(Default, PartialEq)]
+enum Keys {
+    #[default]
+    None,
+    // A string followed by `:`.
+    Json,
+    // `key:` at the start of a line, or after a `- `.
+    Yaml,
+    // `key =` at the start of a line, and `[table]` headers.
+    Toml,
+}
+
+fn syntax(language: &str) -> Option<Syntax> {
+    const JS_KEYWORDS: &[&str] = &[
+        "async",
+        "await",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "debugger",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "export",
+        "extends",
+        "finally",
+        "for",
+        "from",
+        "function",
+        "if",
+        "import",
+        "in",
+        "instanceof",
+        "let",
+        "new",
+        "of",
+        "return",
+        "static",
+        "super",
+        "switch",
+        "this",
+        "throw",
+        "try",
+        "typeof",
+        "var",
+        "void",
+        "while",
+        "with",
+        "yield",
+    ];
+    const JS_LITERALS: &[&str] = &["true", "false", "null", "undefined", "NaN", "Infinity"];
+    let syntax = match language {
+        "rust" | "rs" => Syntax {
+            keywords: &[
+                "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else",
+                "enum", "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+                "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+                "trait", "type", "unsafe", "use", "where", "while",
+            ],
+            types: &[
+                "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16",
+                "i32", "i64", "i128", "isize", "f32", "f64",
+            ],
+            literals: &["true", "false"],
+            line_comments: &["//"],
+            block_comment: Some(("/*", "*/")),
+            quotes: &['"', '\''],
+            capitalized_types: true,
+            calls: true,
+            lifetimes: true,
+            ..Syntax::default()
+        },
+        "python" | "py" => Syntax {
+            keywords: &[
+                "and", "as", "assert", "async", "await", "break", "class", "continue", "def",
+                "del", "elif", "else", "except", "finally", "for", "from", "global", "if",
+                "import", "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return",
+                "try", "while", "with", "yield",
+            ],
+            types: &[
+                "int", "float", "str", "bool", "bytes", "list", "dict", "set", "tuple", "object",
+            ],
+            literals: &["True", "False", "None"],
+            line_comments: &["#"],
+            quotes: &['"', '\''],
+            triple_quotes: true,
+            calls: true,
+            ..Syntax::default()
+        },
+        "javascript" | "js" | "jsx" | "mjs" | "cjs" => Syntax {
+            keywords: JS_KEYWORDS,
+            literals: JS_LITERALS,
+            line_comments: &["//"],
+            block_comment: Some(("/*", "*/")),
+            quotes: &['"', '\'', '`'],
+            calls: true,
+            ..Syntax::default()
+        },
+        "typescript" | "ts" | "tsx" => Syntax {
+            keywords: &[
+                "abstract",
+                "as",
+                "async",
+                "await",
+                "break",
+                "case",
+                "catch",
+                "class",
+                "const",
+                "continue",
+                "declare",
+                "default",
+                "delete",
+                "do",
+                "else",
+                "enum",
+                "export",
+                "extends",
+                "finally",
+                "for",
+                "from",
+                "function",
+                "if",
+                "implements",
+                "import",
+                "in",
+                "instanceof",
+                "interface",
+                "keyof",
+                "let",
+                "namespace",
+                "new",
+                "of",
+                "private",
+                "protected",
+                "public",
+                "readonly",
+                "return",
+                "static",
+                "super",
+                "switch",
+                "this",
+                "throw",
+                "try",
+                "type",
+                "typeof",
+                "var",
+                "void",
+                "while",
+                "yield",
+            ],
+            types: &[
+                "any", "bigint", "boolean", "never", "number", "object", "string", "symbol",
+                "unknown",
+            ],
+            literals: JS_LITERALS,
+            line_comments: &["//"],
+            block_comment: Some(("/*", "*/")),
+            quotes: &['"', '\'', '`'],
+            capitalized_types: true,
+            calls: true,
+            ..Syntax::default()
+        },
+        "shell" | "sh" | "bash" | "zsh" | "console" => Syntax {
+            keywords: &[
+                "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case",
+                "esac", "in", "function", "return", "local", "export", "readonly",
+            ],
+            line_comments: &["#"],
+            quotes: &['"', '\''],
+            raw_single_quotes: true,
+            variables: true,
+            ..Syntax::default()
+        },
+        "json" | "jsonc" => Syntax {
+            literals: &["true", "false", "null"],
+            line_comments: &["//"],
+            quotes: &['"'],
+            keys: Keys::Json,
+            ..Syntax::default()
+        },
+        "toml" => Syntax {
+            literals: &["true", "false"],
+            line_comments: &["#"],
+            quotes: &['"', '\''],
+            triple_quotes: true,
+            raw_single_quotes: true,
+            keys: Keys::Toml,
+            ..Syntax::default()
+        },
+        "yaml" | "yml" => Syntax {
+            literals: &["true", "false", "null", "yes", "no", "on", "off", "~"],
+            line_comments: &["#"],
+            quotes: &['"', '\''],
+            keys: Keys::Yaml,
+            ..Syntax::default()
+        },
+        _ => return None,
+    };
+    Some(syntax)
+}
+
+// A piece of highlighted code: its class, such as `keyword`, or `None` for
+// plain text, and the text.
+pub(crate) type HighlightToken<'a> = (Option<&'static str>, &'a str);
+
+// Highlighted code, built a token at a time.
+#[derive(Default)]
+struct Output<'a> {
+    tokens: Vec<HighlightToken<'a>>,
+}
+
+impl<'a> Output<'a> {
+    fn push(&mut self, class: &'static str, text: &'a str) {
+        if !text.is_empty() {
+            self.tokens.push((Some(class), text));
+        }
+    }
+
+    fn push_plain(&mut self, text: &'a str) {
+        self.tokens.push((None, text));
+    }
+}
+
+fn highlight_with(syntax: Syntax, code: &str) -> Vec<HighlightToken<'_>> {
+    let mut out = Output::default();
+    let mut i = 0;
+    // Whether only indentation (or a YAML `- `) precedes `i` on its line.
+    let mut line_start = true;
+    while i < code.len() {
+        let rest = &code[i..];
+        let c = rest.chars().next().unwrap_or_default();
+        let after_space = code[..i]
+            .chars()
+            .next_back()
+            .is_none_or(char::is_whitespace);
+
+        if c == '\n' || c == ' ' || c == '\t' {
+            line_start |= c == '\n';
+            out.push_plain(&rest[..1]);
+            i += 1;
+            continue;
+        }
+        if line_start && syntax.keys == Keys::Yaml && rest.starts_with("- ") {
+            out.push_plain("- ");
+            i += 2;
+            continue;
+        }
+        let was_line_start = line_start;
+        line_start = false;
+
+        // `#` only starts a comment at the start of a word, so that shell
+        // `a#b` and `${#list}` aren't comments.
+        if let Some(prefix) = syntax.line_comments.iter().find(|p| rest.starts_with(**p))
+            && (!prefix.starts_with('#') || after_space)
+        {
+            let len = rest.find('\n').unwrap_or(rest.len());
+            out.push("comment", &rest[..len]);
+            i += len;
+            continue;
+        }
+        if let Some((open, close)) = syntax.block_comment
+            && rest.starts_with(open)
+        {
+            let len = rest[open.len()..]
+                .find(close)
+                .map_or(rest.len(), |end| open.len() + end + close.len());
+            out.push("comment", &rest[..len]);
+            i += len;
+            continue;
+        }
+        if was_line_start && let Some((class, len)) = line_key(&syntax.keys, rest) {
+            out.push(class, &rest[..len]);
+            i += len;
+            continue;
+        }
+        if syntax.lifetimes && c == '\'' && !is_char_literal(rest) {
+            let len = 1 + word_len(&rest[1..]);
+            out.push_plain(&rest[..len]);
+            i += len;
+            continue;
+        }
+        if syntax.quotes.contains(&c) {
+            let escapes = !(c == '\'' && syntax.raw_single_quotes);
+            let len = string_len(rest, c, syntax.triple_quotes, escapes);
+            let is_key = syntax.keys == Keys::Json && rest[len..].trim_start().starts_with(':');
+            out.push(if is_key { "key" } else { "string" }, &rest[..len]);
+            i += len;
+            continue;
+        }
+        if syntax.variables && c == '$' {
+            let len = variable_len(rest);
+            out.push("variable", &rest[..len]);
+            i += len;
+            continue;
+        }
+        if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+            out.push("number", &rest[..len]);
+            i += len;
+            continue;
+        }
+        // YAML's `~` is a null.
+        if c.is_alphabetic() || c == '_' || (c == '~' && syntax.keys == Keys::Yaml) {
+            let len = word_len(rest).max(c.len_utf8());
+            let word = &rest[..len];
+            let next = rest[len..].chars().next();
+            let class = if syntax.keywords.contains(&word) {
+                Some("keyword")
+            } else if syntax.literals.contains(&word) {
+                Some("literal")
+            } else if syntax.types.contains(&word) || (syntax.capitalized_types && c.is_uppercase())
+            {
+                Some("type")
+            } else if syntax.calls && (next == Some('(') || (syntax.lifetimes && next == Some('!')))
+            {
+                Some("function")
+            } else {
+                None
+            };
+            match class {
+                Some(class) => out.push(class, word),
+                None => out.push_plain(word),
+            }
+            i += len;
+            continue;
+        }
+        out.push_plain(&rest[..c.len_utf8()]);
+        i += c.len_utf8();
+    }
+    out.tokens
+}
+
+// A key at the start of a line of YAML or TOML, and the class and length to
+// highlight.
+fn line_key(keys: &Keys, rest: &str) -> Option<(&'static str, usize)> {
+    let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+    match keys {
+        Keys::Toml if line.starts_with('[') => Some(("type", line.rfind(']')? + 1)),
+        Keys::Toml => {
+            let len = line
+                .find(|c: char| !(c.is_alphanumeric() || "_-.".contains(c)))
+                .unwrap_or(line.len());
+            (len > 0 && line[len..].trim_start().starts_with('=')).then_some(("key", len))
+        }
+        Keys::Yaml => {
+            let len = line
+                .find(": ")
+                .or_else(|| line.strip_suffix(':').map(str::len))?;
+            let key = &line[..len];
+            // Quoted keys and flow collections are left to the scanner.
+            let plain = !key.is_empty()
+                && !key.contains(" #")
+                && !key.starts_with(|c: char| "\"'[{#&*!|>%@`".contains(c));
+            plain.then_some(("key", len))
+        }
+        Keys::Json | Keys::None => None,
+    }
+}
+
+// The length of the identifier `text` starts with.
+fn word_len(text: &str) -> usize {
+    text.find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(text.len())
+}
+
+// Whether `'` starts a Rust char literal like `'a'` or `'\n'`, rather than a
+// lifetime like `'a`.
+fn is_char_literal(text: &str) -> bool {
+    let mut chars = text.chars().skip(1);
+    match chars.next() {
+        Some('\\') => true,
+        Some(_) => chars.next() == Some('\''),
+        None => false,
+    }
+}
+
+// The length of the string literal `text` starts with, including the quotes.
+// Backslash escapes are skipped over if `escapes` is set, and an unclosed
+// string runs to the end.
+fn string_len(text: &str, quote: char, triple_quotes: bool, escapes: bool) -> usize {
+    let triple = quote.to_string().repeat(3);
+    if triple_quotes && text.starts_with(&triple) {
+        return text[3..]
+            .find(&triple)
+            .map_or(text.len(), |end| 3 + end + 3);
+    }
+    let mut chars = text.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' && escapes {
+            chars.next();
+        } else if c == quote {
+            return i + 1;
+        }
+    }
+    text.len()
+}
+
+// The length of the shell variable `text` starts with: `$NAME`, `${...}`, or
+// a special one like `$1` or `$?`.
+fn variable_len(text: &str) -> usize {
+    let rest = &text[1..];
+    if rest.starts_with('{') {
+        return rest.find('}').map_or(text.len(), |end| end + 2);
+    }
+    match rest.chars().next() {
+        Some(c) if c.is_alphabetic() || c == '_' => 1 + word_len(rest),
+        Some(c) if c.is_ascii_digit() || "?!#$@*-".contains(c) => 2,
+        _ => 1,
+    }
+}
+
+// Diffs are highlighted a line at a time.
+fn highlight_diff(code: &str) -> Vec<HighlightToken<'_>> {
+    let mut out = Output::default();
+    for line in code.split_inclusive('\n') {
+        let (text, newline) = match line.strip_suffix('\n') {
+            Some(text) => (text, "\n"),
+            None => (line, ""),
+        };
+        let class = if ["+++", "---", "@@", "diff ", "index "]
+            .iter()
+            .any(|prefix| text.starts_with(prefix))
+        {
+            Some("meta")
+        } else if text.starts_with('+') {
+            Some("inserted")
+        } else if text.starts_with('-') {
+            Some("deleted")
+        } else {
+            None
+        };
+        match class {
+            Some(class) => out.push(class, text),
+            None => out.push_plain(text),
+        }
+        out.push_plain(newline);
+    }
+    out.tokens
+}
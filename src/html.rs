@@ -0,0 +1,414 @@
+// HTML output.
+
+use crate::ast::{Alignment, Node, NodeKind, plain_text};
+use crate::highlight::{BuiltinHighlighter, Highlighter};
+use crate::slug::Slugger;
+use crate::span::Span;
+use crate::toc::{render_toc, table_of_contents};
+use crate::{Options, SoftBreak};
+use std::collections::HashMap;
+
+// Renders the AST to HTML. Holds the options (and, as needed, any state) that
+// rendering depends on.
+pub(crate) struct HtmlRenderer<'a> {
+    options: &'a Options,
+    highlighter: Option<&'a dyn Highlighter>,
+    slugger: Slugger,
+    // The rendered table of contents, if the document has a `[[toc]]`.
+    toc: Option<String>,
+    // The text of each footnote, by lowercased label.
+    footnotes: HashMap<String, Vec<Node>>,
+    // The labels of the footnotes referenced so far, in the order they are
+    // numbered in.
+    footnote_order: Vec<String>,
+    // How many times each footnote has been referenced so far.
+    footnote_refs: HashMap<String, usize>,
+}
+
+impl<'a> HtmlRenderer<'a> {
+    pub(crate) fn new(options: &'a Options) -> Self {
+        HtmlRenderer {
+            options,
+            highlighter: options
+                .syntax_highlighting
+                .then_some(&BuiltinHighlighter as &dyn Highlighter),
+            slugger: Slugger::default(),
+            toc: None,
+            footnotes: HashMap::new(),
+            footnote_order: Vec::new(),
+            footnote_refs: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn highlighter(mut self, highlighter: &'a dyn Highlighter) -> Self {
+        self.highlighter = Some(highlighter);
+        self
+    }
+
+    pub(crate) fn render(&mut self, node: &Node) -> String {
+        match &node.kind {
+            NodeKind::Document(children) => {
+                if children
+                    .iter()
+                    .any(|child| child.kind == NodeKind::TableOfContents)
+                {
+                    self.toc = Some(render_toc(&table_of_contents(node, 1, 6)));
+                }
+                // Footnotes are rendered together at the end, and only if
+                // referenced. The first definition of a label wins.
+                for child in children {
+                    if let NodeKind::FootnoteDefinition { label, children } = &child.kind {
+                        self.footnotes
+                            .entry(label.to_lowercase())
+                            .or_insert_with(|| children.clone());
+                    }
+                }
+                let mut blocks = children
+                    .iter()
+                    .filter(|child| !matches!(child.kind, NodeKind::FootnoteDefinition { .. }))
+                    .map(|child| self.render(child))
+                    .collect::<Vec<String>>();
+                if !self.footnote_order.is_empty() {
+                    blocks.push(self.render_footnotes());
+                }
+                blocks.join("\n")
+            }
+            NodeKind::Heading(level, children) => {
+                // The table of contents links need ids to point at.
+                let ids = self.options.heading_ids || self.options.heading_anchors;
+                let pos = self.sourcepos(node);
+                if !ids && self.toc.is_none() {
+                    return format!(
+                        "<h{}{}>{}</h{}>",
+                        level,
+                        pos,
+                        self.render_all(children),
+                        level
+                    );
+                }
+                let id = escape_attribute(&self.slugger.slug(plain_text(children).trim()));
+                let anchor = if self.options.heading_anchors {
+                    format!(
+                        "<a class=\"anchor\" href=\"#{}\" aria-hidden=\"true\">#</a>",
+                        id
+                    )
+                } else {
+                    String::new()
+                };
+                format!(
+                    "<h{}{} id=\"{}\">{}{}</h{}>",
+                    level,
+                    pos,
+                    id,
+                    anchor,
+                    self.render_all(children),
+                    level
+                )
+            }
+            NodeKind::Paragraph(children) => {
+                format!(
+                    "<p{}>{}</p>",
+                    self.sourcepos(node),
+                    self.render_all(children)
+                )
+            }
+            NodeKind::Bold(children) => {
+                format!("<strong>{}</strong>", self.render_all(children))
+            }
+            NodeKind::Italic(children) => {
+                format!("<em>{}</em>", self.render_all(children))
+            }
+            NodeKind::Strikethrough(children) => {
+                format!("<del>{}</del>", self.render_all(children))
+            }
+            NodeKind::Text(text) => escape_html(text),
+            NodeKind::Link {
+                children,
+                url,
+                title,
+            } => {
+                let title = title
+                    .as_ref()
+                    .map(|title| format!(" title=\"{}\"", escape_attribute(title)))
+                    .unwrap_or_default();
+                let url = if self.options.filter_urls && !is_safe_url(url) {
+                    ""
+                } else {
+                    url
+                };
+                format!(
+                    "<a href=\"{}\"{}>{}</a>",
+                    escape_attribute(&encode_url(url)),
+                    title,
+                    self.render_all(children)
+                )
+            }
+            NodeKind::UnorderedList(children) => {
+                let items = children
+                    .iter()
+                    .map(|child| self.render(child))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                format!("<ul{}>\n{}\n</ul>", self.sourcepos(node), items)
+            }
+            NodeKind::OrderedList { start, children } => {
+                let items = children
+                    .iter()
+                    .map(|child| self.render(child))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                let start = match start {
+                    1 => String::new(),
+                    _ => format!(" start=\"{}\"", start),
+                };
+                format!("<ol{}{}>\n{}\n</ol>", start, self.sourcepos(node), items)
+            }
+            NodeKind::ListItem(children) => {
+                format!(
+                    "<li{}>{}</li>",
+                    self.sourcepos(node),
+                    self.render_all(children)
+                )
+            }
+            NodeKind::TaskMarker(checked) => {
+                let checked = if *checked { " checked=\"\"" } else { "" };
+                format!(
+                    "<input type=\"checkbox\"{} disabled=\"\"{}",
+                    checked,
+                    self.void_end()
+                )
+            }
+            NodeKind::CodeBlock { info, content } => {
+                let language = info.split_whitespace().next().unwrap_or_default();
+                let Some(highlighter) = self.highlighter.filter(|_| !language.is_empty()) else {
+                    return format!(
+                        "<pre{}><code>{}</code></pre>",
+                        self.sourcepos(node),
+                        escape_html(content)
+                    );
+                };
+                let code = highlighter
+                    .highlight(language, content)
+                    .unwrap_or_else(|| escape_html(content));
+                format!(
+                    "<pre{}><code class=\"language-{}\">{}</code></pre>",
+                    self.sourcepos(node),
+                    escape_attribute(language),
+                    code
+                )
+            }
+            NodeKind::Code(code) => {
+                format!("<code>{}</code>", escape_html(code))
+            }
+            NodeKind::HtmlBlock(html) if self.options.raw_html => html.clone(),
+            // Escaped, the block reads like the paragraph it used to be.
+            NodeKind::HtmlBlock(html) => {
+                format!("<p{}>{}</p>", self.sourcepos(node), escape_html(html))
+            }
+            NodeKind::HtmlInline(html) if self.options.raw_html => html.clone(),
+            NodeKind::HtmlInline(html) => escape_html(html),
+            NodeKind::SoftBreak => match self.options.soft_breaks {
+                SoftBreak::Newline => "\n".to_string(),
+                SoftBreak::Space => " ".to_string(),
+                SoftBreak::Break => format!("<br{}\n", self.void_end()),
+            },
+            NodeKind::TableOfContents => {
+                let toc = self.toc.clone().unwrap_or_default();
+                match toc.strip_prefix("<ul>") {
+                    Some(rest) => format!("<ul{}>{}", self.sourcepos(node), rest),
+                    None => toc,
+                }
+            }
+            NodeKind::Table {
+                alignments,
+                children,
+            } => {
+                let mut rows = children.iter();
+                let mut html = format!("<table{}>\n", self.sourcepos(node));
+                if let Some(header) = rows.next() {
+                    let header = self.render_table_row(header, alignments, "th");
+                    html.push_str(&format!("<thead>\n{}\n</thead>\n", header));
+                }
+                let body = rows
+                    .map(|row| self.render_table_row(row, alignments, "td"))
+                    .collect::<Vec<String>>();
+                if !body.is_empty() {
+                    html.push_str(&format!("<tbody>\n{}\n</tbody>\n", body.join("\n")));
+                }
+                html.push_str("</table>");
+                html
+            }
+            NodeKind::TableRow(_) => self.render_table_row(node, &[], "td"),
+            NodeKind::TableCell(children) => {
+                format!(
+                    "<td{}>{}</td>",
+                    self.sourcepos(node),
+                    self.render_all(children)
+                )
+            }
+            NodeKind::FootnoteReference(label) => {
+                let key = label.to_lowercase();
+                if !self.footnotes.contains_key(&key) {
+                    return escape_html(&format!("[^{}]", label));
+                }
+                let number = match self.footnote_order.iter().position(|l| *l == key) {
+                    Some(index) => index + 1,
+                    None => {
+                        self.footnote_order.push(key.clone());
+                        self.footnote_order.len()
+                    }
+                };
+                let count = self.footnote_refs.entry(key.clone()).or_default();
+                *count += 1;
+                let id = if *count == 1 {
+                    format!("fnref-{}", key)
+                } else {
+                    format!("fnref-{}-{}", key, count)
+                };
+                format!(
+                    "<sup class=\"footnote-ref\"><a href=\"#fn-{}\" id=\"{}\" data-footnote-ref>{}</a></sup>",
+                    escape_attribute(&key),
+                    escape_attribute(&id),
+                    number
+                )
+            }
+            // Rendered by `render_footnotes` instead.
+            NodeKind::FootnoteDefinition { .. } => String::new(),
+        }
+    }
+
+    pub(crate) fn render_all(&mut self, nodes: &[Node]) -> String {
+        nodes.iter().map(|node| self.render(node)).collect()
+    }
+
+    // Renders a table row, with `tag` (`th` or `td`) for its cells.
+    fn render_table_row(&mut self, row: &Node, alignments: &[Alignment], tag: &str) -> String {
+        let NodeKind::TableRow(cells) = &row.kind else {
+            return self.render(row);
+        };
+        let cells = cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                let align = match alignments.get(i) {
+                    Some(Alignment::Left) => " align=\"left\"",
+                    Some(Alignment::Center) => " align=\"center\"",
+                    Some(Alignment::Right) => " align=\"right\"",
+                    Some(Alignment::None) | None => "",
+                };
+                let content = match &cell.kind {
+                    NodeKind::TableCell(children) => self.render_all(children),
+                    _ => self.render(cell),
+                };
+                format!(
+                    "<{}{}{}>{}</{}>",
+                    tag,
+                    self.sourcepos(cell),
+                    align,
+                    content,
+                    tag
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+        format!("<tr{}>\n{}\n</tr>", self.sourcepos(row), cells)
+    }
+
+    // The section listing the referenced footnotes by number, each with a
+    // link back to its first reference. Footnotes can reference further
+    // footnotes, which get added to the end of the list as it is rendered.
+    fn render_footnotes(&mut self) -> String {
+        let mut items = Vec::new();
+        let mut i = 0;
+        while i < self.footnote_order.len() {
+            let key = self.footnote_order[i].clone();
+            let children = self.footnotes[&key].clone();
+            let id = escape_attribute(&key);
+            items.push(format!(
+                "<li id=\"fn-{}\">\n<p>{} <a href=\"#fnref-{}\" class=\"footnote-backref\" data-footnote-backref aria-label=\"Back to content\">↩</a></p>\n</li>",
+                id,
+                self.render_all(&children),
+                id
+            ));
+            i += 1;
+        }
+        format!(
+            "<section class=\"footnotes\" data-footnotes>\n<ol>\n{}\n</ol>\n</section>",
+            items.join("\n")
+        )
+    }
+
+    // How void elements like `<br>` are closed.
+    fn void_end(&self) -> &'static str {
+        if self.options.xhtml { " />" } else { ">" }
+    }
+
+    // The `data-sourcepos` attribute for a block element, if enabled. Like
+    // cmark's, the end column is that of the last character, not after it.
+    fn sourcepos(&self, node: &Node) -> String {
+        if !self.options.source_positions {
+            return String::new();
+        }
+        let Span { start, end } = node.span;
+        format!(
+            " data-sourcepos=\"{}:{}-{}:{}\"",
+            start.line,
+            start.column,
+            end.line,
+            end.column.saturating_sub(1)
+        )
+    }
+}
+
+pub(crate) fn render(node: &Node) -> String {
+    HtmlRenderer::new(&Options::default()).render(node)
+}
+
+#[cfg(test)]
+pub(crate) fn render_all(nodes: &[Node]) -> String {
+    HtmlRenderer::new(&Options::default()).render_all(nodes)
+}
+
+/// Escapes `&`, `<` and `>`, for putting text into HTML.
+pub fn escape_html(text: &str) -> String {
+    text.replace("&", "&amp;")
+        .replace("<", "&lt;")
+        .replace(">", "&gt;")
+}
+
+/// Escapes text for a double-quoted HTML attribute value: like
+/// [`escape_html`], and `"` as well.
+pub fn escape_attribute(text: &str) -> String {
+    escape_html(text).replace('"', "&quot;")
+}
+
+// Whether a link destination is safe to put in an `href`. It isn't with a
+// scheme that runs script or reads local files. Browsers ignore tabs and
+// newlines in a scheme, and leading whitespace, so this does too.
+fn is_safe_url(url: &str) -> bool {
+    let url = url
+        .trim_start_matches(|c: char| c <= ' ')
+        .replace(['\t', '\n', '\r'], "")
+        .to_ascii_lowercase();
+    let Some((scheme, _)) = url.split_once(':') else {
+        return true;
+    };
+    !matches!(scheme, "javascript" | "vbscript" | "file" | "data")
+}
+
+// Percent-encodes characters that cannot appear in an href as-is, such as the
+// spaces allowed in `<...>` destinations. Existing escapes are left alone.
+fn encode_url(url: &str) -> String {
+    let mut encoded = String::with_capacity(url.len());
+    for ch in url.chars() {
+        if ch.is_ascii_alphanumeric() || "-_.!~*'();/?:@&=+$,#%[]".contains(ch) {
+            encoded.push(ch);
+        } else {
+            let mut buf = [0; 4];
+            for byte in ch.encode_utf8(&mut buf).bytes() {
+                encoded.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+    encoded
+}
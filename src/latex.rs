@@ -0,0 +1,320 @@
+// LaTeX output: the body of a LaTeX document, for typesetting Markdown as a
+// PDF with a TeX install.
+
+use crate::ast::{Alignment, Document, Node, NodeKind};
+use crate::plain::strip_tags;
+use std::collections::{HashMap, HashSet};
+
+/// The packages the output of [`render_latex`] uses, for the preamble of
+/// the document it goes in: `hyperref` for links, `listings` for code
+/// blocks and `ulem` for struck-through text.
+pub const LATEX_PACKAGES: &str = "\
+\\usepackage{hyperref}
+\\usepackage{listings}
+\\usepackage[normalem]{ulem}
+";
+
+/// Writes a parsed document out as LaTeX, to go between
+/// `\begin{document}` and `\end{document}`:
+///
+/// ```
+/// let document = mark_html::parse_document("# Results\n\nA *50%* gain, see [the data](https://example.com/#q3).");
+/// assert_eq!(
+///     mark_html::render_latex(&document),
+///     "\\section{Results}\n\nA \\emph{50\\%} gain, see \\href{https://example.com/\\#q3}{the data}.\n"
+/// );
+/// ```
+///
+/// Headings become `\section` down to `\subparagraph`, lists `itemize` or
+/// `enumerate`, code blocks `verbatim`, or `lstlisting` when they name a
+/// language, and footnotes `\footnote`. LaTeX's special characters in text
+/// are escaped. Raw HTML can't be typeset, so only the text between its tags
+/// is kept.
+/// See [`LATEX_PACKAGES`] for the packages the output needs.
+pub fn render_latex(document: &Document) -> String {
+    let mut renderer = LatexRenderer::default();
+    renderer.collect_footnotes(document);
+    let latex = renderer.render(document);
+    if latex.is_empty() {
+        latex
+    } else {
+        latex + "\n"
+    }
+}
+
+#[derive(Default)]
+struct LatexRenderer<'a> {
+    // Footnote definitions by lowercased label, written where they are
+    // referenced.
+    footnotes: HashMap<String, &'a [Node]>,
+    // The labels of the footnotes being written out, so that footnotes
+    // citing each other aren't expanded forever.
+    expanding: HashSet<String>,
+}
+
+impl<'a> LatexRenderer<'a> {
+    fn collect_footnotes(&mut self, node: &'a Node) {
+        match &node.kind {
+            NodeKind::FootnoteDefinition { label, children } => {
+                self.footnotes
+                    .entry(label.to_lowercase())
+                    .or_insert(children);
+            }
+            NodeKind::Document(children) => children
+                .iter()
+                .for_each(|child| self.collect_footnotes(child)),
+            _ => {}
+        }
+    }
+
+    fn render(&mut self, node: &Node) -> String {
+        match &node.kind {
+            NodeKind::Document(children) => children
+                .iter()
+                .map(|child| self.render(child))
+                .filter(|latex| !latex.is_empty())
+                .collect::<Vec<String>>()
+                .join("\n\n"),
+            NodeKind::Heading(level, children) => {
+                let command = match level {
+                    1 => "section",
+                    2 => "subsection",
+                    3 => "subsubsection",
+                    4 => "paragraph",
+                    _ => "subparagraph",
+                };
+                let title = self.render_inlines(children);
+                format!("\\{}{{{}}}", command, title.trim())
+            }
+            NodeKind::Paragraph(children) => self.render_inlines(children),
+            NodeKind::UnorderedList(children) => {
+                let items = children
+                    .iter()
+                    .map(|child| self.render(child))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                format!("\\begin{{itemize}}\n{}\n\\end{{itemize}}", items)
+            }
+            NodeKind::OrderedList { start, children } => {
+                let mut items = children
+                    .iter()
+                    .map(|child| self.render(child))
+                    .collect::<Vec<String>>();
+                // The counter is stepped before each item is numbered.
+                if *start != 1 {
+                    items.insert(
+                        0,
+                        format!("  \\setcounter{{enumi}}{{{}}}", *start as i64 - 1),
+                    );
+                }
+                format!(
+                    "\\begin{{enumerate}}\n{}\n\\end{{enumerate}}",
+                    items.join("\n")
+                )
+            }
+            NodeKind::ListItem(children) => match children.split_first() {
+                Some((
+                    Node {
+                        kind: NodeKind::TaskMarker(checked),
+                        ..
+                    },
+                    rest,
+                )) => {
+                    let marker = if *checked { "[x]" } else { "[ ]" };
+                    let text = self.render_inlines(rest);
+                    format!("  \\item[{{{}}}] {}", marker, text.trim_start())
+                }
+                _ => {
+                    // `\item` would take text starting with `[` as its label.
+                    let text = self.render_inlines(children);
+                    let item = if text.starts_with('[') {
+                        "\\item{}"
+                    } else {
+                        "\\item"
+                    };
+                    format!("  {} {}", item, text)
+                }
+            },
+            NodeKind::CodeBlock { info, content } => {
+                let newline = if content.is_empty() || content.ends_with('\n') {
+                    ""
+                } else {
+                    "\n"
+                };
+                let language = info.split_whitespace().next().unwrap_or_default();
+                let environment = if language.is_empty() {
+                    "verbatim"
+                } else {
+                    "lstlisting"
+                };
+                // Code that would end the environment early is typeset line by
+                // line instead, escaped like any other text.
+                if content.contains(&format!("\\end{{{}}}", environment)) {
+                    return content
+                        .lines()
+                        .map(|line| format!("\\texttt{{{}}}", escape(line).replace(' ', "\\ ")))
+                        .collect::<Vec<String>>()
+                        .join("\\\\\n");
+                }
+                if language.is_empty() {
+                    format!(
+                        "\\begin{{verbatim}}\n{}{}\\end{{verbatim}}",
+                        content, newline
+                    )
+                } else {
+                    // `listings` stops with an error at a language it doesn't
+                    // know, so those are left unnamed.
+                    let option = match listings_language(language) {
+                        Some(language) => format!("[language={}]", language),
+                        None => String::new(),
+                    };
+                    format!(
+                        "\\begin{{lstlisting}}{}\n{}{}\\end{{lstlisting}}",
+                        option, content, newline
+                    )
+                }
+            }
+            NodeKind::HtmlBlock(html) => escape(strip_tags(html).trim()),
+            NodeKind::TableOfContents => "\\tableofcontents".to_string(),
+            NodeKind::Table {
+                alignments,
+                children,
+            } => {
+                let columns = alignments
+                    .iter()
+                    .map(|alignment| match alignment {
+                        Alignment::None | Alignment::Left => 'l',
+                        Alignment::Center => 'c',
+                        Alignment::Right => 'r',
+                    })
+                    .collect::<String>();
+                let mut rows = children.iter().map(|row| self.render(row));
+                let header = rows.next().unwrap_or_default();
+                let body = rows.collect::<Vec<String>>();
+                let mut lines = vec![
+                    format!("\\begin{{tabular}}{{{}}}", columns),
+                    "\\hline".to_string(),
+                    header,
+                    "\\hline".to_string(),
+                ];
+                lines.extend(body);
+                if lines.len() > 4 {
+                    lines.push("\\hline".to_string());
+                }
+                lines.push("\\end{tabular}".to_string());
+                lines.join("\n")
+            }
+            NodeKind::TableRow(cells) => {
+                let cells = cells
+                    .iter()
+                    .map(|cell| self.render(cell))
+                    .collect::<Vec<String>>();
+                format!("{} \\\\", cells.join(" & "))
+            }
+            NodeKind::TableCell(children) => self.render_inlines(children),
+            // Written out where they are referenced.
+            NodeKind::FootnoteDefinition { .. } => String::new(),
+            _ => self.render_inlines(std::slice::from_ref(node)),
+        }
+    }
+
+    fn render_inlines(&mut self, nodes: &[Node]) -> String {
+        let mut latex = String::new();
+        for node in nodes {
+            match &node.kind {
+                NodeKind::Text(text) => latex.push_str(&escape(text)),
+                NodeKind::SoftBreak => latex.push('\n'),
+                NodeKind::Bold(children) => {
+                    latex.push_str(&format!("\\textbf{{{}}}", self.render_inlines(children)))
+                }
+                NodeKind::Italic(children) => {
+                    latex.push_str(&format!("\\emph{{{}}}", self.render_inlines(children)))
+                }
+                NodeKind::Strikethrough(children) => {
+                    latex.push_str(&format!("\\sout{{{}}}", self.render_inlines(children)))
+                }
+                NodeKind::Code(code) => latex.push_str(&format!("\\texttt{{{}}}", escape(code))),
+                NodeKind::Link { children, url, .. } => latex.push_str(&format!(
+                    "\\href{{{}}}{{{}}}",
+                    escape_url(url),
+                    self.render_inlines(children)
+                )),
+                NodeKind::TaskMarker(checked) => {
+                    latex.push_str(if *checked { "[x]" } else { "[ ]" })
+                }
+                NodeKind::FootnoteReference(label) => {
+                    let key = label.to_lowercase();
+                    match self.footnotes.get(&key).copied() {
+                        Some(children) if self.expanding.insert(key.clone()) => {
+                            let note = self.render_inlines(children);
+                            self.expanding.remove(&key);
+                            latex.push_str(&format!("\\footnote{{{}}}", note));
+                        }
+                        _ => latex.push_str(&escape(&format!("[^{}]", label))),
+                    }
+                }
+                NodeKind::HtmlInline(_) => {}
+                _ => latex.push_str(&self.render(node)),
+            }
+        }
+        latex
+    }
+}
+
+// Escapes the characters LaTeX treats specially in text. `<`, `>` and `|`
+// are written as commands too, since the default font encoding has no
+// glyphs for them.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '<' => escaped.push_str("\\textless{}"),
+            '>' => escaped.push_str("\\textgreater{}"),
+            '|' => escaped.push_str("\\textbar{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Escapes a URL for `\href`, which takes most characters as they are.
+fn escape_url(url: &str) -> String {
+    let mut escaped = String::with_capacity(url.len());
+    for c in url.chars() {
+        if matches!(c, '\\' | '#' | '%' | '{' | '}') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// The `listings` name for a language named in an info string, if it has one
+// built in.
+fn listings_language(language: &str) -> Option<&'static str> {
+    let name = match language.to_ascii_lowercase().as_str() {
+        "c" => "C",
+        "cpp" | "c++" => "C++",
+        "java" => "Java",
+        "python" | "py" => "Python",
+        "sh" | "bash" | "shell" | "zsh" => "bash",
+        "sql" => "SQL",
+        "html" => "HTML",
+        "xml" => "XML",
+        "ruby" | "rb" => "Ruby",
+        "perl" => "Perl",
+        "php" => "PHP",
+        "haskell" | "hs" => "Haskell",
+        "tex" | "latex" => "TeX",
+        "make" | "makefile" => "make",
+        _ => return None,
+    };
+    Some(name)
+}
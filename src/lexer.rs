@@ -0,0 +1,1340 @@
+// Turns the input into a flat list of tokens, each with the span of input it
+// came from. Constructs that are decided line by line (fences, indented code,
+// HTML blocks) are recognized here; grouping lines into blocks is left to the
+// parser.
+
+use crate::Options;
+use crate::ast::Alignment;
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::raw_html::{HtmlBlockEnd, html_block_start, scan_inline_html};
+use crate::span::{Cursor, Position, Span};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Token {
+    Heading(usize),
+    // A run of `*` or `_` characters, or of `~` with strikethrough enabled.
+    // Whether it opens or closes emphasis is decided later in
+    // `parse_inlines`, based on the flanking flags.
+    Delimiter {
+        ch: char,
+        count: usize,
+        can_open: bool,
+        can_close: bool,
+    },
+    Text(String),
+    NewLine,
+    // The tokens of the link text come between these two, so it can hold
+    // inline formatting.
+    LinkStart,
+    LinkEnd {
+        url: String,
+        title: Option<String>,
+    },
+    ListItemStart,
+    // The `1.` or `1)` of an ordered list item, with its number.
+    OrderedItemStart(usize),
+    TaskMarker(bool),
+    CodeBlock {
+        info: String,
+        code: String,
+    },
+    CodeSpan(String),
+    HtmlBlock(String),
+    HtmlInline(String),
+    Table(Table),
+    FootnoteRef(String),
+    // Starts the line holding the text of a footnote.
+    FootnoteDefinition(String),
+}
+
+// A table, lexed as a whole since its rows aren't separate blocks.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Table {
+    pub(crate) alignments: Vec<Alignment>,
+    // The header row comes first. Every row has a cell for each column.
+    pub(crate) rows: Vec<TableRow>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct TableRow {
+    pub(crate) span: Span,
+    pub(crate) cells: Vec<TableCell>,
+}
+
+// A cell's content is lexed on its own, like link text.
+#[derive(Debug, PartialEq)]
+pub(crate) struct TableCell {
+    pub(crate) span: Span,
+    pub(crate) tokens: Vec<Token>,
+    pub(crate) spans: Vec<Span>,
+}
+
+// A link reference definition, `[label]: url "title"`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LinkDefinition {
+    url: String,
+    title: Option<String>,
+}
+
+// Link definitions by normalized label.
+pub(crate) type Definitions = BTreeMap<String, LinkDefinition>;
+
+static NO_DEFINITIONS: Definitions = BTreeMap::new();
+
+// The output of the lexer. `spans[i]` is the span of `tokens[i]`.
+pub(crate) struct Lexed {
+    pub(crate) tokens: Vec<Token>,
+    pub(crate) spans: Vec<Span>,
+    pub(crate) diagnostics: Vec<Diagnostic>,
+    pub(crate) definitions: Definitions,
+}
+
+#[cfg(test)]
+pub(crate) fn lex(input: &str) -> Vec<Token> {
+    lex_document(Cursor::new(input), &Options::default()).tokens
+}
+
+// Lexes a whole document. Links may refer to definitions further down, so if
+// there are any, the input is lexed a second time knowing all of them.
+pub(crate) fn lex_document(chars: Cursor, options: &Options) -> Lexed {
+    let first = Lexer::new(chars.clone(), options, &NO_DEFINITIONS).run();
+    if first.definitions.is_empty() {
+        return first;
+    }
+    Lexer::new(chars, options, &first.definitions).run()
+}
+
+struct Lexer<'a> {
+    chars: Cursor<'a>,
+    tokens: Vec<Token>,
+    // The span of each token, in step with `tokens`.
+    spans: Vec<Span>,
+    diagnostics: Vec<Diagnostic>,
+    // Which extensions to recognize.
+    options: &'a Options,
+    // The definitions reference links are resolved against.
+    definitions: &'a Definitions,
+    // The definitions found so far. The first definition of a label wins.
+    defined: Definitions,
+    // Set when lexing link text, where nothing is at the start of a line.
+    inline_only: bool,
+    // The links whose text is being lexed, innermost last.
+    links: Vec<LinkText<'a>>,
+    // Set for a paragraph line indented by 4 or more columns, whose content
+    // can't start a block even once the indentation is skipped.
+    indented_continuation: bool,
+    // The `]` matching each `[`, by the offset of the `[`.
+    brackets: HashMap<usize, Position>,
+    // Where each paragraph ends. Nothing inline runs past one.
+    paragraph_ends: Vec<usize>,
+    // The end of the line last asked for by `line_end`.
+    line_end: Option<Position>,
+}
+
+// A link whose text is being lexed.
+struct LinkText<'a> {
+    // The index of its `LinkStart` token.
+    token: usize,
+    // Where the text ends, and where the link does.
+    text_end: Position,
+    after: Position,
+    url: String,
+    title: Option<String>,
+    // The cursor and `inline_only` to go back to after the link.
+    outer: Cursor<'a>,
+    inline_only: bool,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(chars: Cursor<'a>, options: &'a Options, definitions: &'a Definitions) -> Self {
+        let (brackets, paragraph_ends) = scan_brackets(chars.clone(), options);
+        Lexer {
+            chars,
+            tokens: Vec::new(),
+            spans: Vec::new(),
+            diagnostics: Vec::new(),
+            options,
+            definitions,
+            defined: Definitions::new(),
+            inline_only: false,
+            links: Vec::new(),
+            indented_continuation: false,
+            brackets,
+            paragraph_ends,
+            line_end: None,
+        }
+    }
+
+    fn run(mut self) -> Lexed {
+        loop {
+            let start = self.chars.location();
+            let Some(c) = self.chars.next() else {
+                if self.links.is_empty() {
+                    break;
+                }
+                self.end_link();
+                continue;
+            };
+            self.lex_token(c, start);
+        }
+        Lexed {
+            tokens: self.tokens,
+            spans: self.spans,
+            diagnostics: self.diagnostics,
+            definitions: self.defined,
+        }
+    }
+
+    // Adds a token that started at `start` and ends at the current position.
+    fn push(&mut self, token: Token, start: Position) {
+        let end = self.chars.location();
+        self.push_span(token, Span { start, end });
+    }
+
+    fn push_span(&mut self, token: Token, span: Span) {
+        self.tokens.push(token);
+        self.spans.push(span);
+    }
+
+    // A copy of the cursor moved to `position`, for lookahead that has to
+    // stop at the end of the paragraph.
+    fn in_paragraph(&self, position: Position) -> Cursor<'a> {
+        let next = self
+            .paragraph_ends
+            .partition_point(|&end| end <= position.offset);
+        let end = self.paragraph_ends.get(next).copied();
+        self.chars.seek(position).until(end.unwrap_or(usize::MAX))
+    }
+
+    // A cursor just past the `]` at `text_end`, for reading what follows the
+    // link text.
+    fn after_link_text(&self, text_end: Position) -> Cursor<'a> {
+        let mut after = self.in_paragraph(text_end);
+        after.next();
+        after
+    }
+
+    // Where the current line ends. It is remembered, as a long line can have
+    // many diagnostics running to its end.
+    fn line_end(&mut self) -> Position {
+        match self.line_end {
+            Some(end) if end.offset >= self.chars.location().offset => end,
+            _ => *self.line_end.insert(line_end(&self.chars)),
+        }
+    }
+
+    // Starts a link whose text ends at `text_end`, continuing at `after`. The
+    // text is lexed on its own, up to `text_end`, where `end_link` takes over.
+    fn push_link(
+        &mut self,
+        start: Position,
+        text_end: Position,
+        after: Position,
+        url: String,
+        title: Option<String>,
+    ) {
+        self.push(Token::LinkStart, start);
+        self.links.push(LinkText {
+            token: self.tokens.len() - 1,
+            text_end,
+            after,
+            url,
+            title,
+            outer: self.chars.clone(),
+            inline_only: self.inline_only,
+        });
+        self.chars = self.chars.until(text_end.offset);
+        self.inline_only = true;
+        self.line_end = None;
+    }
+
+    // Ends the innermost link, once its text has been lexed.
+    fn end_link(&mut self) {
+        let Some(link) = self.links.pop() else {
+            return;
+        };
+        self.chars = link.outer.seek(link.after);
+        self.inline_only = link.inline_only;
+        self.line_end = None;
+        let (url, title) = (link.url, link.title);
+        self.push(Token::LinkEnd { url, title }, link.text_end);
+        self.unnest_links();
+    }
+
+    // Turns the links around one that was just added back into text, since
+    // links don't nest and the inner link wins. Their text is lexed on as
+    // ordinary text, without stopping at the `]`.
+    fn unnest_links(&mut self) {
+        let Some(outermost) = self.links.first() else {
+            return;
+        };
+        self.chars = outermost.outer.seek(self.chars.location());
+        self.inline_only = outermost.inline_only;
+        self.line_end = None;
+        for link in std::mem::take(&mut self.links) {
+            self.tokens[link.token] = Token::Text("[".to_string());
+        }
+    }
+
+    // Lexes the token starting with `c`, which has just been consumed.
+    fn lex_token(&mut self, c: char, start: Position) {
+        let line_start =
+            !self.inline_only && at_line_start(&self.tokens) && !self.indented_continuation;
+        if line_start {
+            let line = format!("{}{}", c, peek_line(&self.chars));
+            if let Some(fence) = fence_start(&line) {
+                let opening = Span {
+                    start,
+                    end: start.advance(line.len()),
+                };
+                let (code, closed) = lex_fenced_code(line, &mut self.chars, &fence);
+                let info = fence.info;
+                if !closed {
+                    self.diagnostics.push(Diagnostic::new(
+                        Severity::Error,
+                        opening,
+                        "unterminated-code-fence",
+                        "code fence is never closed, so the rest of the document is code"
+                            .to_string(),
+                    ));
+                }
+                self.push(Token::CodeBlock { info, code }, start);
+                return;
+            }
+            if let Some(end) = html_block_start(&line, continues_paragraph(&self.tokens)) {
+                let html = lex_html_block(line, &mut self.chars, end);
+                self.push(Token::HtmlBlock(html), start);
+                return;
+            }
+            // Unlike most blocks these can interrupt a paragraph, so that
+            // footnotes can be defined one per line.
+            if self.options.footnotes
+                && let Some((label, len)) = scan_footnote_definition(&line)
+            {
+                self.chars.skip_to(start.offset + len);
+                self.push(Token::FootnoteDefinition(label), start);
+                return;
+            }
+            if self.options.tables
+                && !continues_paragraph(&self.tokens)
+                && let Some(alignments) = table_start(&line, self.chars.seek(start).rest())
+            {
+                self.lex_table(&line, start, alignments);
+                return;
+            }
+            // Definitions produce no output, the line reads as blank.
+            if !continues_paragraph(&self.tokens)
+                && let Some((label, definition)) = scan_definition(&line)
+            {
+                for _ in line.chars().skip(1) {
+                    self.chars.next();
+                }
+                self.defined.entry(label).or_insert(definition);
+                return;
+            }
+            // Only a list starting at 1 can interrupt a paragraph, so that a
+            // number ending a line of text doesn't start one.
+            if let Some((number, len)) = ordered_item_start(&line)
+                && (number == 1 || !continues_paragraph(&self.tokens))
+            {
+                self.chars.skip_to(start.offset + len);
+                self.push(Token::OrderedItemStart(number), start);
+                if self.options.task_lists {
+                    self.lex_task_marker();
+                }
+                return;
+            }
+            // Otherwise leading whitespace doesn't matter, except that indented
+            // code can't interrupt a paragraph, there the indented line is
+            // just more paragraph text.
+            if c == ' ' || c == '\t' {
+                let indent = indent_width(&line);
+                if indent >= 4 && !line.trim().is_empty() {
+                    if !continues_paragraph(&self.tokens) {
+                        let code = lex_indented_code(line, &mut self.chars);
+                        let info = String::new();
+                        self.push(Token::CodeBlock { info, code }, start);
+                        return;
+                    }
+                    self.indented_continuation = true;
+                }
+                while self.chars.peek().is_some_and(|&c| c == ' ' || c == '\t') {
+                    self.chars.next();
+                }
+                return;
+            }
+        }
+        match c {
+            '#' if line_start => {
+                let mut level = 1;
+                while let Some('#') = self.chars.peek() {
+                    self.chars.next();
+                    level += 1;
+                }
+                // Headings are typically followed by a space
+                if self.chars.peek() == Some(&' ') {
+                    self.chars.next();
+                }
+                self.push(Token::Heading(level), start);
+            }
+            '-' | '*' | '+' if line_start && self.chars.peek() == Some(&' ') => {
+                self.chars.next(); // consume the space
+                self.push(Token::ListItemStart, start);
+                if self.options.task_lists {
+                    self.lex_task_marker();
+                }
+            }
+            '*' | '_' => {
+                let mut count = 1;
+                while self.chars.peek() == Some(&c) {
+                    self.chars.next();
+                    count += 1;
+                }
+                let prev = last_char(&self.tokens);
+                let next = self.chars.peek().copied();
+                self.push(delimiter_run(c, count, prev, next), start);
+            }
+            '\n' => {
+                self.indented_continuation = false;
+                self.push(Token::NewLine, start);
+            }
+            '[' => {
+                if self.options.footnotes
+                    && let Some(label) = scan_footnote_reference(self.chars.rest())
+                {
+                    self.chars.skip_to(start.offset + label.len() + 3);
+                    self.push(Token::FootnoteRef(label), start);
+                    return;
+                }
+                // Only commit to the link if the whole `[text](url)` is there,
+                // or the text refers to a link definition; otherwise the
+                // bracket is literal and lexing resumes after it.
+                if let Some(&text_end) = self.brackets.get(&start.offset) {
+                    let mut lookahead = self.after_link_text(text_end);
+                    if let Some((url, title)) = scan_link_destination_and_title(&mut lookahead) {
+                        self.push_link(start, text_end, lookahead.location(), url, title);
+                        return;
+                    }
+                    let lookahead = self.after_link_text(text_end);
+                    let text = self.chars.until(text_end.offset).rest();
+                    let mut after_label = lookahead.clone();
+                    match scan_reference_label(&mut after_label) {
+                        // `[text][label]`, or `[text][]` to use the text as label.
+                        Some(label) => {
+                            let label = if label.is_empty() { text } else { &label };
+                            match self.definitions.get(&normalize_label(label)) {
+                                Some(definition) => {
+                                    let (url, title) =
+                                        (definition.url.clone(), definition.title.clone());
+                                    let after = after_label.location();
+                                    self.push_link(start, text_end, after, url, title);
+                                    return;
+                                }
+                                None => self.diagnostics.push(Diagnostic::new(
+                                    Severity::Warning,
+                                    Span {
+                                        start,
+                                        end: after_label.location(),
+                                    },
+                                    "unresolved-reference",
+                                    format!("no link definition for `[{}]`", label),
+                                )),
+                            }
+                        }
+                        // A plain `[text]` is only a link if it has been defined.
+                        None => {
+                            if let Some(definition) = self.definitions.get(&normalize_label(text)) {
+                                let (url, title) =
+                                    (definition.url.clone(), definition.title.clone());
+                                let after = lookahead.location();
+                                self.push_link(start, text_end, after, url, title);
+                                return;
+                            }
+                            // `[text](` promises a link, so it is probably a
+                            // mistake if none follows.
+                            if lookahead.peek() == Some(&'(') {
+                                let end = self.line_end();
+                                self.diagnostics.push(Diagnostic::new(
+                                    Severity::Warning,
+                                    Span { start, end },
+                                    "unclosed-link",
+                                    "link is not closed, so it shows up as text".to_string(),
+                                ));
+                            }
+                        }
+                    }
+                }
+                self.push(Token::Text("[".to_string()), start);
+            }
+            '-' => self.push(Token::Text("-".to_string()), start),
+            '`' => {
+                let mut count = 1;
+                while self.chars.peek() == Some(&'`') {
+                    self.chars.next();
+                    count += 1;
+                }
+                let mut lookahead = self.chars.clone();
+                match scan_code_span(&mut lookahead, count) {
+                    Some(code) => {
+                        self.chars = lookahead;
+                        self.push(Token::CodeSpan(code), start);
+                    }
+                    None => self.push(Token::Text("`".repeat(count)), start),
+                }
+            }
+            '~' if self.options.strikethrough => {
+                let mut count = 1;
+                while self.chars.peek() == Some(&'~') {
+                    self.chars.next();
+                    count += 1;
+                }
+                if count > 2 {
+                    self.push(Token::Text("~".repeat(count)), start);
+                } else {
+                    let prev = last_char(&self.tokens);
+                    let next = self.chars.peek().copied();
+                    self.push(delimiter_run('~', count, prev, next), start);
+                }
+            }
+            '<' => {
+                if self.options.autolinks
+                    && let Some((len, url)) = scan_autolink(self.chars.rest())
+                {
+                    let text_start = self.chars.location();
+                    let text = self.chars.rest()[..len].to_string();
+                    self.push(Token::LinkStart, start);
+                    self.chars.skip_to(text_start.offset + len);
+                    self.push(Token::Text(text), text_start);
+                    let text_end = self.chars.location();
+                    self.chars.next(); // the closing `>`
+                    self.push(Token::LinkEnd { url, title: None }, text_end);
+                    self.unnest_links();
+                    return;
+                }
+                let mut lookahead = self.in_paragraph(self.chars.location());
+                match scan_inline_html(&mut lookahead) {
+                    Some(html) => {
+                        self.chars = self.chars.seek(lookahead.location());
+                        self.push(Token::HtmlInline(html), start);
+                    }
+                    None => self.push(Token::Text("<".to_string()), start),
+                }
+            }
+            _ => {
+                // Like GitHub, bare URLs aren't links inside link text.
+                if self.options.extended_autolinks
+                    && self.links.is_empty()
+                    && starts_word(last_char(&self.tokens))
+                    && let Some((len, url)) = extended_autolink(self.chars.seek(start).rest())
+                {
+                    let end = start.advance(len);
+                    let text = self.chars.seek(start).rest()[..len].to_string();
+                    self.chars.skip_to(end.offset);
+                    self.push_span(Token::LinkStart, Span { start, end: start });
+                    self.push_span(Token::Text(text), Span { start, end });
+                    self.push_span(
+                        Token::LinkEnd { url, title: None },
+                        Span { start: end, end },
+                    );
+                    return;
+                }
+                let mut buff = String::new();
+                buff.push(c);
+                while let Some(&next) = self.chars.peek() {
+                    if self.ends_text(next, &buff) {
+                        break;
+                    }
+                    buff.push(self.chars.next().unwrap());
+                }
+                self.push(Token::Text(buff), start);
+            }
+        }
+    }
+
+    // Whether text stops before `next`, as something else may start there.
+    fn ends_text(&self, next: char, text: &str) -> bool {
+        match next {
+            '#' | '*' | '_' | '\n' | '[' | '-' | '`' | '<' => true,
+            '~' => self.options.strikethrough,
+            'w' | 'h' => {
+                self.options.extended_autolinks
+                    && self.links.is_empty()
+                    && starts_word(text.chars().next_back())
+                    && extended_autolink(self.chars.rest()).is_some()
+            }
+            _ => false,
+        }
+    }
+
+    // Lexes `[ ]` or `[x]` at the start of a list item, if it is followed by
+    // whitespace.
+    fn lex_task_marker(&mut self) {
+        let rest = self.chars.rest();
+        let checked = match rest.get(..3) {
+            Some("[ ]") => false,
+            Some("[x]" | "[X]") => true,
+            _ => return,
+        };
+        if !rest[3..].starts_with([' ', '\t']) {
+            return;
+        }
+        let start = self.chars.location();
+        self.chars.skip_to(start.offset + 3);
+        self.push(Token::TaskMarker(checked), start);
+    }
+
+    // Lexes a table whose header row, `line`, starts at `start`, and whose
+    // delimiter row comes next. Rows run up to a blank line or the start of
+    // another block.
+    fn lex_table(&mut self, line: &str, start: Position, alignments: Vec<Alignment>) {
+        let mut rows = vec![self.lex_table_row(line, start, alignments.len())];
+        self.chars.skip_to(start.offset + line.len());
+        self.chars.next();
+        let delimiter_row = peek_line(&self.chars);
+        self.chars
+            .skip_to(self.chars.location().offset + delimiter_row.len());
+        while self.chars.peek() == Some(&'\n') {
+            let mut lookahead = self.chars.clone();
+            lookahead.next();
+            let row = peek_line(&lookahead);
+            if row.trim().is_empty() || starts_block(&row) {
+                break;
+            }
+            let row_start = lookahead.location();
+            rows.push(self.lex_table_row(&row, row_start, alignments.len()));
+            lookahead.skip_to(row_start.offset + row.len());
+            self.chars = lookahead;
+        }
+        self.push(Token::Table(Table { alignments, rows }), start);
+    }
+
+    // Lexes the cells of the table row `line`, which starts at `start`. Rows
+    // are padded with empty cells, or cut down, to `columns` cells.
+    fn lex_table_row(&mut self, line: &str, start: Position, columns: usize) -> TableRow {
+        let end = start.advance(line.len());
+        let mut cells = Vec::new();
+        for (from, to) in split_table_row(line).into_iter().take(columns) {
+            let mut chars = self.chars.seek(start);
+            chars.skip_to(start.offset + from);
+            let cell_start = chars.location();
+            let mut cell = Lexer::new(
+                chars.until(start.offset + to),
+                self.options,
+                self.definitions,
+            );
+            cell.inline_only = true;
+            let mut cell = cell.run();
+            // `\|` is how a cell holds a pipe.
+            for token in &mut cell.tokens {
+                if let Token::Text(text) = token {
+                    *text = text.replace("\\|", "|");
+                }
+            }
+            self.diagnostics.append(&mut cell.diagnostics);
+            cells.push(TableCell {
+                span: Span {
+                    start: cell_start,
+                    end: cell_start.advance(to - from),
+                },
+                tokens: cell.tokens,
+                spans: cell.spans,
+            });
+        }
+        while cells.len() < columns {
+            cells.push(TableCell {
+                span: Span { start: end, end },
+                tokens: Vec::new(),
+                spans: Vec::new(),
+            });
+        }
+        TableRow {
+            span: Span { start, end },
+            cells,
+        }
+    }
+}
+
+fn at_line_start(tokens: &[Token]) -> bool {
+    matches!(tokens.last(), None | Some(Token::NewLine))
+}
+
+// Whether the line before the current one is paragraph text, which some
+// blocks are not allowed to interrupt. A list item is a line of its own, the
+// parser ends it at the line end as it does a heading.
+fn continues_paragraph(tokens: &[Token]) -> bool {
+    let Some((Token::NewLine, before)) = tokens.split_last() else {
+        return false;
+    };
+    let line_start = before
+        .iter()
+        .rposition(|t| *t == Token::NewLine)
+        .map_or(0, |p| p + 1);
+    !matches!(
+        before.get(line_start),
+        None | Some(
+            Token::Heading(_)
+                | Token::ListItemStart
+                | Token::OrderedItemStart(_)
+                | Token::CodeBlock { .. }
+                | Token::HtmlBlock(_)
+                | Token::Table(_)
+        )
+    )
+}
+
+// Column width of the leading whitespace, with tab stops every 4 columns.
+fn indent_width(line: &str) -> usize {
+    let mut width = 0;
+    for c in line.chars() {
+        match c {
+            ' ' => width += 1,
+            '\t' => width += 4 - width % 4,
+            _ => break,
+        }
+    }
+    width
+}
+
+// Removes up to `columns` columns of leading whitespace. A tab that is only
+// partly removed leaves the rest of its width behind as spaces.
+fn strip_indent(line: &str, columns: usize) -> String {
+    let mut width = 0;
+    for (i, c) in line.char_indices() {
+        if width >= columns {
+            return line[i..].to_string();
+        }
+        match c {
+            ' ' => width += 1,
+            '\t' => {
+                width += 4 - width % 4;
+                if width > columns {
+                    return " ".repeat(width - columns) + &line[i + 1..];
+                }
+            }
+            _ => return line[i..].to_string(),
+        }
+    }
+    String::new()
+}
+
+// The opening line of a fenced code block.
+struct Fence {
+    ch: char,
+    len: usize,
+    indent: usize,
+    info: String,
+}
+
+// A fence is a line of at least three backticks or tildes, indented by no more
+// than three spaces and followed by an optional info string. The info string
+// of a backtick fence can't contain backticks, so ``` `a` ``` stays inline.
+fn fence_start(line: &str) -> Option<Fence> {
+    let rest = line.trim_start_matches(' ');
+    let indent = line.len() - rest.len();
+    let ch = rest.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let len = rest.len() - rest.trim_start_matches(ch).len();
+    if indent > 3 || len < 3 || (ch == '`' && rest[len..].contains('`')) {
+        return None;
+    }
+    Some(Fence {
+        ch,
+        len,
+        indent,
+        info: rest[len..].trim().to_string(),
+    })
+}
+
+// A closing fence uses the same character as the opening one, is at least as
+// long, and has nothing but whitespace after it.
+fn is_closing_fence(line: &str, fence: &Fence) -> bool {
+    let rest = line.trim_start_matches(' ');
+    let run = rest.len() - rest.trim_start_matches(fence.ch).len();
+    line.len() - rest.len() <= 3 && run >= fence.len && rest[run..].trim().is_empty()
+}
+
+// Consumes a fenced code block starting with `line`, whose first character has
+// already been consumed. Content lines lose as much indentation as the opening
+// fence had. Without a closing fence, the block runs to the end of the input.
+fn lex_fenced_code(line: String, chars: &mut Cursor, fence: &Fence) -> (String, bool) {
+    for _ in line.chars().skip(1) {
+        chars.next();
+    }
+    let mut code = String::new();
+    while chars.peek() == Some(&'\n') {
+        chars.next();
+        let line = peek_line(chars);
+        for _ in line.chars() {
+            chars.next();
+        }
+        if is_closing_fence(&line, fence) {
+            return (code, true);
+        }
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        code.push_str(&line[indent.min(fence.indent)..]);
+        code.push('\n');
+    }
+    (code, false)
+}
+
+// Consumes an indented code block starting with `line`, whose first character
+// has already been consumed. Blank lines inside the block are kept, trailing
+// ones are not, and neither is the newline after the block.
+fn lex_indented_code(line: String, chars: &mut Cursor) -> String {
+    for _ in line.chars().skip(1) {
+        chars.next();
+    }
+    let mut code = strip_indent(&line, 4) + "\n";
+    let mut blank_lines = String::new();
+    let mut lookahead = chars.clone();
+    while lookahead.next() == Some('\n') {
+        let line = peek_line(&lookahead);
+        if line.trim().is_empty() {
+            blank_lines.push_str(&strip_indent(&line, 4));
+            blank_lines.push('\n');
+        } else if indent_width(&line) >= 4 {
+            code.push_str(&blank_lines);
+            blank_lines.clear();
+            code.push_str(&strip_indent(&line, 4));
+            code.push('\n');
+        } else {
+            break;
+        }
+        for _ in line.chars() {
+            lookahead.next();
+        }
+        if blank_lines.is_empty() {
+            *chars = lookahead.clone();
+        }
+    }
+    code
+}
+
+// The rest of the current line, without consuming it.
+fn peek_line(chars: &Cursor) -> String {
+    let rest = chars.rest();
+    rest[..rest.find('\n').unwrap_or(rest.len())].to_string()
+}
+
+// The position at the end of the current line.
+fn line_end(chars: &Cursor) -> Position {
+    let mut chars = chars.clone();
+    while chars.peek().is_some_and(|&c| c != '\n') {
+        chars.next();
+    }
+    chars.location()
+}
+
+// Consumes an HTML block starting with `line`, whose first character has
+// already been consumed. The newline after the block is left alone.
+fn lex_html_block(mut line: String, chars: &mut Cursor, end: HtmlBlockEnd) -> String {
+    for _ in line.chars().skip(1) {
+        chars.next();
+    }
+    let mut html = String::new();
+    loop {
+        html.push_str(&line);
+        if end.ends_on(&line) || chars.peek() != Some(&'\n') {
+            break;
+        }
+        let mut lookahead = chars.clone();
+        lookahead.next();
+        let next_line = peek_line(&lookahead);
+        if end == HtmlBlockEnd::BlankLine && next_line.trim().is_empty() {
+            break;
+        }
+        chars.next();
+        for _ in next_line.chars() {
+            chars.next();
+        }
+        html.push('\n');
+        line = next_line;
+    }
+    html
+}
+
+// Scans the `(url "title")` after the text of an inline link.
+fn scan_link_destination_and_title(chars: &mut Cursor) -> Option<(String, Option<String>)> {
+    if chars.next()? != '(' {
+        return None;
+    }
+    skip_link_whitespace(chars);
+    let url = scan_link_destination(chars)?;
+
+    // A title has to be separated from the destination by whitespace.
+    let had_space = skip_link_whitespace(chars);
+    let title = match chars.peek()? {
+        '"' | '\'' | '(' if had_space => {
+            let title = scan_link_title(chars)?;
+            skip_link_whitespace(chars);
+            Some(title)
+        }
+        _ => None,
+    };
+
+    if chars.next()? != ')' {
+        return None;
+    }
+    Some((url, title))
+}
+
+// Pairs up the brackets in `chars`, in one pass so that many unmatched `[`
+// don't each scan to the end of the input. Brackets inside link text must be
+// balanced, as in `[see [1]](url)`.
+//
+// Also returns where paragraphs end, which no bracket pairs across: at a blank
+// line, at a line starting another block, and after that line, since headings
+// and list items take up just the one.
+fn scan_brackets(mut chars: Cursor, options: &Options) -> (HashMap<usize, Position>, Vec<usize>) {
+    let mut brackets = HashMap::new();
+    let mut paragraph_ends = Vec::new();
+    let mut open = Vec::new();
+    let mut line_start = true;
+    let mut after_block_start = false;
+    loop {
+        let position = chars.location();
+        if line_start {
+            let line = peek_line(&chars);
+            let block_start = interrupts_paragraph(&line, options);
+            if block_start || after_block_start || line.trim().is_empty() {
+                paragraph_ends.push(position.offset);
+                open.clear();
+            }
+            after_block_start = block_start;
+        }
+        let Some(c) = chars.next() else {
+            break;
+        };
+        line_start = c == '\n';
+        match c {
+            '[' => open.push(position.offset),
+            ']' => {
+                if let Some(offset) = open.pop() {
+                    brackets.insert(offset, position);
+                }
+            }
+            _ => {}
+        }
+    }
+    (brackets, paragraph_ends)
+}
+
+// Whether `line` starts a block that ends a table.
+fn starts_block(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    fence_start(line).is_some()
+        || html_block_start(line, true).is_some()
+        || trimmed.starts_with('#')
+        || ["- ", "* ", "+ "]
+            .iter()
+            .any(|bullet| trimmed.starts_with(bullet))
+        || ordered_item_start(trimmed).is_some_and(|(number, _)| number == 1)
+}
+
+// The number of the ordered list item `line` starts, such as `1.` or `2)`
+// followed by a space, and the length of that marker and space. Numbers have
+// at most 9 digits, as in CommonMark.
+fn ordered_item_start(line: &str) -> Option<(usize, usize)> {
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if !(1..=9).contains(&digits) || !line[digits..].starts_with(['.', ')']) {
+        return None;
+    }
+    if !line[digits + 1..].starts_with(' ') {
+        return None;
+    }
+    Some((line[..digits].parse().ok()?, digits + 2))
+}
+
+// Whether `line` starts a block even right after a line of paragraph text.
+// Indented by 4 or more columns, it is more of the paragraph.
+fn interrupts_paragraph(line: &str, options: &Options) -> bool {
+    indent_width(line) < 4
+        && (starts_block(line) || (options.footnotes && scan_footnote_definition(line).is_some()))
+}
+
+// The column alignments, if `line` is the header row of a table. It is when
+// the line after it in `rest` is a delimiter row with as many cells, like
+// `| --- | :-: |`.
+fn table_start(line: &str, rest: &str) -> Option<Vec<Alignment>> {
+    let next = rest.get(line.len()..)?.strip_prefix('\n')?;
+    let next = &next[..next.find('\n').unwrap_or(next.len())];
+    if !line.contains('|') || !next.contains('|') {
+        return None;
+    }
+    let alignments = split_table_row(next)
+        .into_iter()
+        .map(|(from, to)| {
+            let cell = &next[from..to];
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+                return None;
+            }
+            Some(match (cell.starts_with(':'), cell.ends_with(':')) {
+                (true, true) => Alignment::Center,
+                (true, false) => Alignment::Left,
+                (false, true) => Alignment::Right,
+                (false, false) => Alignment::None,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (alignments.len() == split_table_row(line).len()).then_some(alignments)
+}
+
+// The byte ranges of the cells of a table row, without the whitespace around
+// them. Pipes at the start and end of the row are optional, and `\|` doesn't
+// separate cells.
+fn split_table_row(line: &str) -> Vec<(usize, usize)> {
+    let end = line.trim_end().len();
+    let mut start = (line.len() - line.trim_start().len()).min(end);
+    if line[start..end].starts_with('|') {
+        start += 1;
+    }
+    let mut ranges = Vec::new();
+    let mut cell_start = start;
+    let mut escaped = false;
+    for (i, c) in line[start..end].char_indices() {
+        if c == '|' && !escaped {
+            ranges.push((cell_start, start + i));
+            cell_start = start + i + 1;
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    // A trailing pipe ends the last cell rather than starting another.
+    if cell_start < end || ranges.is_empty() {
+        ranges.push((cell_start, end));
+    }
+    ranges
+        .into_iter()
+        .map(|(from, to)| {
+            let cell = &line[from..to];
+            let from = from + cell.len() - cell.trim_start().len();
+            (from, from + cell.trim().len())
+        })
+        .collect()
+}
+
+// The label of a `[^label]` footnote reference, given the text after `[`.
+fn scan_footnote_reference(rest: &str) -> Option<String> {
+    let rest = rest.strip_prefix('^')?;
+    let label = &rest[..rest.find(|c: char| c.is_whitespace() || c == '[' || c == ']')?];
+    if label.is_empty() || !rest[label.len()..].starts_with(']') {
+        return None;
+    }
+    Some(label.to_string())
+}
+
+// The label of a `[^label]: text` footnote definition, and the length of the
+// line up to its text.
+fn scan_footnote_definition(line: &str) -> Option<(String, usize)> {
+    let rest = line.trim_start_matches(' ');
+    if line.len() - rest.len() > 3 {
+        return None;
+    }
+    let label = scan_footnote_reference(rest.strip_prefix('[')?)?;
+    let text = rest[label.len() + 3..].strip_prefix(':')?;
+    let text = text.trim_start_matches([' ', '\t']);
+    Some((label, line.len() - text.len()))
+}
+
+// An autolink after its `<`: an absolute URI like `<https://example.com>` or
+// an email address, up to the `>`. Returns the length of the link text and
+// the destination.
+pub(crate) fn scan_autolink(rest: &str) -> Option<(usize, String)> {
+    let text =
+        &rest[..rest.find(|c: char| c.is_whitespace() || c.is_control() || "<>".contains(c))?];
+    if !rest[text.len()..].starts_with('>') {
+        return None;
+    }
+    if let Some((scheme, _)) = text.split_once(':')
+        && (2..=32).contains(&scheme.len())
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c))
+    {
+        return Some((text.len(), text.to_string()));
+    }
+    let (local, domain) = text.split_once('@')?;
+    let local_ok = !local.is_empty()
+        && local
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ".!#$%&'*+/=?^_`{|}~-".contains(c));
+    let domain_ok = domain.split('.').all(|label| {
+        (1..=63).contains(&label.len())
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+    });
+    (local_ok && domain_ok).then(|| (text.len(), format!("mailto:{}", text)))
+}
+
+// Bare URLs are only recognized at the start of a word, or after emphasis
+// delimiters and opening parentheses.
+pub(crate) fn starts_word(prev: Option<char>) -> bool {
+    prev.is_none_or(|c| c.is_whitespace() || "*_~(".contains(c))
+}
+
+// A bare URL at the start of `text`: `www.` or `http(s)://` and a domain, plus
+// an optional path. Trailing punctuation and unmatched closing parentheses are
+// left out, as GitHub does. Returns its length and destination.
+pub(crate) fn extended_autolink(text: &str) -> Option<(usize, String)> {
+    let prefix = ["www.", "http://", "https://"]
+        .into_iter()
+        .find(|prefix| text.starts_with(prefix))?;
+    let mut link = &text[..text
+        .find(|c: char| c.is_whitespace() || c == '<')
+        .unwrap_or(text.len())];
+    loop {
+        if let Some(trimmed) = link.strip_suffix(['?', '!', '.', ',', ':', '*', '_', '~']) {
+            link = trimmed;
+        } else if link.ends_with(')') && link.matches(')').count() > link.matches('(').count() {
+            link = &link[..link.len() - 1];
+        } else {
+            break;
+        }
+    }
+    let rest = &link[prefix.len()..];
+    let domain = &rest[..rest.find(['/', '?', '#']).unwrap_or(rest.len())];
+    let valid = !domain.is_empty()
+        && (prefix == "www." || domain.contains('.'))
+        && domain.split('.').all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        });
+    if !valid {
+        return None;
+    }
+    let url = if prefix == "www." {
+        format!("http://{}", link)
+    } else {
+        link.to_string()
+    };
+    Some((link.len(), url))
+}
+
+// Scans the `[label]` that follows the text of a full or collapsed reference
+// link. The label is empty for `[]`.
+fn scan_reference_label(chars: &mut Cursor) -> Option<String> {
+    if chars.next()? != '[' {
+        return None;
+    }
+    let mut label = String::new();
+    loop {
+        match chars.next()? {
+            ']' => return Some(label),
+            '[' => return None,
+            ch => label.push(ch),
+        }
+    }
+}
+
+// Labels match case-insensitively, with runs of whitespace counting as one space.
+fn normalize_label(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+// A link reference definition, `[label]: url "title"`, indented by at most
+// three spaces and taking up the whole line.
+fn scan_definition(line: &str) -> Option<(String, LinkDefinition)> {
+    let rest = line.trim_start_matches(' ');
+    if line.len() - rest.len() > 3 {
+        return None;
+    }
+    let mut chars = Cursor::new(rest.strip_prefix('[')?);
+    let mut label = String::new();
+    loop {
+        match chars.next()? {
+            ']' => break,
+            '[' => return None,
+            ch => label.push(ch),
+        }
+    }
+    if label.trim().is_empty() || chars.next()? != ':' {
+        return None;
+    }
+    skip_link_whitespace(&mut chars);
+    let url = scan_link_destination(&mut chars).filter(|url| !url.is_empty())?;
+    let had_space = skip_link_whitespace(&mut chars);
+    let title = match chars.peek() {
+        Some('"' | '\'' | '(') if had_space => Some(scan_link_title(&mut chars)?),
+        _ => None,
+    };
+    skip_link_whitespace(&mut chars);
+    if chars.peek().is_some() {
+        return None;
+    }
+    Some((normalize_label(&label), LinkDefinition { url, title }))
+}
+
+// Skips spaces and tabs plus at most one line ending. Returns whether
+// anything was skipped.
+fn skip_link_whitespace(chars: &mut Cursor) -> bool {
+    let mut skipped = false;
+    let mut seen_newline = false;
+    while let Some(&ch) = chars.peek() {
+        match ch {
+            ' ' | '\t' => {}
+            '\n' if !seen_newline => seen_newline = true,
+            _ => break,
+        }
+        chars.next();
+        skipped = true;
+    }
+    skipped
+}
+
+// Either `<anything but line breaks>` or a run of non-whitespace characters
+// in which parentheses are balanced, like `https://en.wikipedia.org/wiki/Rust_(programming_language)`.
+// Like cmark, this gives up past 32 levels of parentheses, so that a long run
+// of `[a](` doesn't scan to its end for every link.
+fn scan_link_destination(chars: &mut Cursor) -> Option<String> {
+    let mut url = String::new();
+    if chars.peek() == Some(&'<') {
+        chars.next();
+        loop {
+            match chars.next()? {
+                '>' => return Some(url),
+                '<' | '\n' => return None,
+                '\\' => url.push(scan_escaped(chars)),
+                ch => url.push(ch),
+            }
+        }
+    }
+
+    let mut depth = 0;
+    while let Some(&ch) = chars.peek() {
+        match ch {
+            ')' if depth == 0 => break,
+            ')' => depth -= 1,
+            '(' if depth == 32 => return None,
+            '(' => depth += 1,
+            _ if ch.is_whitespace() || ch.is_control() => break,
+            _ => {}
+        }
+        chars.next();
+        if ch == '\\' {
+            url.push(scan_escaped(chars));
+        } else {
+            url.push(ch);
+        }
+    }
+    (depth == 0).then_some(url)
+}
+
+// A title in double quotes, single quotes or parentheses.
+fn scan_link_title(chars: &mut Cursor) -> Option<String> {
+    let close = match chars.next()? {
+        '(' => ')',
+        quote => quote,
+    };
+    let mut title = String::new();
+    loop {
+        match chars.next()? {
+            ch if ch == close => return Some(title),
+            '(' if close == ')' => return None,
+            '\\' => title.push(scan_escaped(chars)),
+            ch => title.push(ch),
+        }
+    }
+}
+
+// Resolves the character after a backslash. Only ASCII punctuation can be
+// escaped; anything else keeps the backslash.
+fn scan_escaped(chars: &mut Cursor) -> char {
+    match chars.peek() {
+        Some(&ch) if ch.is_ascii_punctuation() => {
+            chars.next();
+            ch
+        }
+        _ => '\\',
+    }
+}
+
+// Scans a code span after its opening run of `count` backticks, up to a
+// closing run of exactly the same length on the same line.
+fn scan_code_span(chars: &mut Cursor, count: usize) -> Option<String> {
+    let mut code = String::new();
+    loop {
+        match chars.next()? {
+            '\n' => return None,
+            '`' => {
+                let mut run = 1;
+                while chars.peek() == Some(&'`') {
+                    chars.next();
+                    run += 1;
+                }
+                if run == count {
+                    break;
+                }
+                code.push_str(&"`".repeat(run));
+            }
+            ch => code.push(ch),
+        }
+    }
+
+    // A single surrounding space is stripped, so `` `a` `` can hold backticks.
+    if code.len() >= 2 && code.starts_with(' ') && code.ends_with(' ') && code.trim() != "" {
+        code = code[1..code.len() - 1].to_string();
+    }
+    Some(code)
+}
+
+// The character just before the current lexer position, as far as emphasis
+// flanking is concerned. Block markers count as whitespace.
+fn last_char(tokens: &[Token]) -> Option<char> {
+    match tokens.last()? {
+        Token::Text(text) => text.chars().next_back(),
+        Token::Delimiter { ch, .. } => Some(*ch),
+        Token::LinkEnd { .. } => Some(')'),
+        Token::TaskMarker(_) | Token::FootnoteRef(_) => Some(']'),
+        Token::CodeSpan(_) => Some('`'),
+        Token::HtmlInline(_) => Some('>'),
+        Token::Heading(_)
+        | Token::NewLine
+        | Token::LinkStart
+        | Token::Table(_)
+        | Token::FootnoteDefinition(_)
+        | Token::ListItemStart
+        | Token::OrderedItemStart(_)
+        | Token::CodeBlock { .. }
+        | Token::HtmlBlock(_) => None,
+    }
+}
+
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation() || !(c.is_alphanumeric() || c.is_whitespace() || c.is_control())
+}
+
+// Classifies a delimiter run using the CommonMark left/right-flanking rules.
+// The start and end of a line count as whitespace.
+fn delimiter_run(ch: char, count: usize, prev: Option<char>, next: Option<char>) -> Token {
+    let prev_space = prev.is_none_or(char::is_whitespace);
+    let next_space = next.is_none_or(char::is_whitespace);
+    let prev_punct = prev.is_some_and(is_punctuation);
+    let next_punct = next.is_some_and(is_punctuation);
+
+    let left_flanking = !next_space && (!next_punct || prev_space || prev_punct);
+    let right_flanking = !prev_space && (!prev_punct || next_space || next_punct);
+
+    let (can_open, can_close) = if ch == '_' {
+        // `_` may not be used for intraword emphasis, so `snake_case` stays literal.
+        (
+            left_flanking && (!right_flanking || prev_punct),
+            right_flanking && (!left_flanking || next_punct),
+        )
+    } else {
+        (left_flanking, right_flanking)
+    };
+    Token::Delimiter {
+        ch,
+        count,
+        can_open,
+        can_close,
+    }
+}
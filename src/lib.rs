@@ -1,383 +1,142 @@
 #[cfg(test)]
 pub mod test;
-#[derive(Debug, PartialEq)]
-enum Token {
-    Heading(usize),
-    BoldStart,
-    BoldEnd,
-    ItalicStart,
-    ItalicEnd,
-    Text(String),
-    NewLine,
-    Link { text: String, url: String },
-    ListItemStart,
-    CodeBlock(String),
-}
 
-#[derive(Debug, PartialEq)]
-enum Node {
-    Document(Vec<Node>),
-    Heading(usize, Vec<Node>),
-    Paragraph(Vec<Node>),
-    Bold(Vec<Node>),
-    Italic(Vec<Node>),
-    Text(String),
-    Link { text: String, url: String },
-    UnorderedList(Vec<Node>),
-    ListItem(Vec<Node>),
-    CodeBlock(String),
-}
+mod ast;
+mod diagnostic;
+mod front_matter;
+mod highlight;
+mod html;
+mod latex;
+mod legacy;
+mod lexer;
+mod markdown;
+mod options;
+mod page;
+mod parser;
+mod plain;
+mod raw_html;
+mod site;
+mod slug;
+mod span;
+mod terminal;
+mod toc;
+
+pub use ast::{Alignment, Document, Node, NodeKind};
+pub use diagnostic::{Diagnostic, ParseError, Severity};
+use front_matter::split_front_matter;
+pub use front_matter::{Metadata, Value};
+pub use highlight::{BuiltinHighlighter, HIGHLIGHT_CSS, Highlighter};
+#[cfg(test)]
+use html::render_all;
+use html::{HtmlRenderer, render};
+pub use html::{escape_attribute, escape_html};
+pub use latex::{LATEX_PACKAGES, render_latex};
+use lexer::{Lexed, lex_document};
+#[cfg(test)]
+use lexer::{Token, lex};
+pub use markdown::{Style, format_markdown, render_markdown, render_markdown_with_options};
+pub use options::{Options, Preset, SoftBreak};
+pub use page::{Page, to_html_page};
+#[cfg(test)]
+use parser::parse;
+use parser::parse_spanned;
+pub use plain::{to_plain_text, to_plain_text_with_options};
+pub use site::{BuildReport, Site};
+use span::Cursor;
+pub use span::{Position, Span};
+pub use terminal::{Terminal, render_terminal};
+pub use toc::{TocEntry, render_toc, table_of_contents};
 
 pub fn to_html(input: &str) -> String {
-    let tokens = lex(input);
-    let ast = parse(&tokens);
-    render(&ast)
+    render(&parse_document(input))
 }
 
-fn lex(input: &str) -> Vec<Token> {
-    let mut tokens = Vec::new();
-    let mut chars = input.chars().peekable();
-    let mut bold_active = false;
-    let mut italic_active = false;
-    while let Some(c) = chars.next() {
-        match c {
-            '#' => {
-                let mut level = 1;
-                while let Some('#') = chars.peek() {
-                    chars.next();
-                    level += 1;
-                }
-                // Headings are typically followed by a space
-                if chars.peek() == Some(&' ') {
-                    chars.next();
-                }
-                tokens.push(Token::Heading(level));
-            }
-            '*' => {
-                if chars.peek() == Some(&'*') {
-                    chars.next(); // consume the second '*'
-                    if bold_active {
-                        tokens.push(Token::BoldEnd);
-                    } else {
-                        tokens.push(Token::BoldStart);
-                    }
-                    bold_active = !bold_active;
-                } else {
-                    if italic_active {
-                        tokens.push(Token::ItalicEnd);
-                    } else {
-                        tokens.push(Token::ItalicStart);
-                    }
-                    italic_active = !italic_active;
-                }
-            }
-            '\n' => {
-                tokens.push(Token::NewLine);
-            }
-            '[' => {
-                let mut text = String::new();
-                while let Some(&ch) = chars.peek() {
-                    if ch == ']' {
-                        break;
-                    }
-                    text.push(chars.next().unwrap());
-                }
-
-                // Check for the full link syntax: [text](url)
-                if chars.peek() == Some(&']') {
-                    chars.next(); // consume ']'
-                    if chars.peek() == Some(&'(') {
-                        chars.next(); // consume '('
-                        let mut url = String::new();
-                        while let Some(&ch) = chars.peek() {
-                            if ch == ')' {
-                                break;
-                            }
-                            url.push(chars.next().unwrap());
-                        }
-                        if chars.peek() == Some(&')') {
-                            chars.next(); // consume ')'
-                            tokens.push(Token::Link { text, url });
-                        } else {
-                            // This is a malformed link, like [text](url
-                            // Treat all parts as plain text.
-                            tokens.push(Token::Text("[".to_string()));
-                            tokens.push(Token::Text(text));
-                            tokens.push(Token::Text("]".to_string()));
-                            tokens.push(Token::Text("(".to_string()));
-                            tokens.push(Token::Text(url));
-                        }
-                    } else {
-                        // This is just text in brackets, like [text]
-                        tokens.push(Token::Text("[".to_string()));
-                        tokens.push(Token::Text(text));
-                        tokens.push(Token::Text("]".to_string()));
-                    }
-                } else {
-                    // No closing bracket found, like [text
-                    tokens.push(Token::Text("[".to_string()));
-                    tokens.push(Token::Text(text));
-                }
-            }
-            '-' => {
-                if chars.peek() == Some(&' ') {
-                    chars.next(); // consume the space
-                    tokens.push(Token::ListItemStart);
-                } else {
-                    tokens.push(Token::Text("-".to_string()));
-                }
-            }
-            '`' => {
-                let mut p = chars.clone();
-                if p.next() == Some('`') && p.next() == Some('`') {
-                    // Consume the ```
-                    chars.next();
-                    chars.next();
+/// Parses Markdown into its AST without rendering it. Front matter is skipped,
+/// but spans still count from the very start of `input`.
+pub fn parse_document(input: &str) -> Document {
+    parse_document_with_options(input, &Options::default())
+}
 
-                    // Consume optional language specifier, and the rest of the line.
-                    while let Some(c) = chars.peek() {
-                        if *c == '\n' {
-                            break;
-                        }
-                        chars.next();
-                    }
-                    if chars.peek() == Some(&'\n') {
-                        chars.next(); // Consume the newline
-                    }
+/// Parses Markdown like [`parse_document`], with the syntax extensions enabled
+/// in `options`.
+pub fn parse_document_with_options(input: &str, options: &Options) -> Document {
+    split_and_parse(input, options).1
+}
 
-                    let mut code = String::new();
-                    'code_block: loop {
-                        if let Some('`') = chars.peek() {
-                            let mut p2 = chars.clone();
-                            p2.next(); // `
-                            if let Some('`') = p2.peek() {
-                                p2.next(); // `
-                                if let Some('`') = p2.peek() {
-                                    // Found end fence
-                                    chars.next();
-                                    chars.next();
-                                    chars.next();
-                                    break 'code_block;
-                                }
-                            }
-                        }
+/// Parses Markdown like [`parse_document`], and also reports markup that is
+/// probably broken: code fences that are never closed, links missing their
+/// closing `)`, reference links without a definition and emphasis that is
+/// never closed. The document is the same either way. Diagnostics are in the
+/// order they appear in the input.
+pub fn parse_with_diagnostics(input: &str) -> (Document, Vec<Diagnostic>) {
+    let (_, document, diagnostics) = split_and_parse(input, &Options::default());
+    (document, diagnostics)
+}
 
-                        if let Some(c) = chars.next() {
-                            code.push(c);
-                        } else {
-                            // Unterminated
-                            break 'code_block;
-                        }
-                    }
-                    tokens.push(Token::CodeBlock(code));
-                } else {
-                    tokens.push(Token::Text("`".to_string()));
-                }
-            }
-            _ => {
-                let mut buff = String::new();
-                buff.push(c);
-                while let Some(&next) = chars.peek() {
-                    if next == '#'
-                        || next == '*'
-                        || next == '\n'
-                        || next == '['
-                        || next == '-'
-                        || next == '`'
-                    {
-                        break;
-                    }
-                    buff.push(chars.next().unwrap());
-                }
-                tokens.push(Token::Text(buff));
-            }
-        }
+/// Converts Markdown to HTML like [`to_html`], with the given [`Options`].
+pub fn to_html_with_options(input: &str, options: &Options) -> String {
+    if options.legacy_02 {
+        return legacy::to_html(input);
     }
-    tokens
+    HtmlRenderer::new(options).render(&parse_document_with_options(input, options))
 }
 
-fn parse(tokens: &[Token]) -> Node {
-    let mut nodes = Vec::new();
-    let mut i = 0;
-    while i < tokens.len() {
-        if let Some(Token::CodeBlock(content)) = tokens.get(i) {
-            nodes.push(Node::CodeBlock(content.clone()));
-            i += 1;
-            continue;
-        }
-
-        let end_of_line = tokens[i..]
-            .iter()
-            .position(|t| *t == Token::NewLine)
-            .map_or(tokens.len(), |p| i + p);
-        let line_tokens = &tokens[i..end_of_line];
-
-        if line_tokens.is_empty() {
-            i = end_of_line + 1;
-            continue;
-        }
-
-        match &line_tokens[0] {
-            Token::Heading(level) => {
-                let content = parse_inlines(&line_tokens[1..]);
-                nodes.push(Node::Heading(*level, content));
-            }
-            Token::ListItemStart => {
-                let mut list_items = Vec::new();
-
-                // First item
-                let item_content = parse_inlines(&line_tokens[1..]);
-                list_items.push(Node::ListItem(item_content));
-                i = end_of_line + 1;
-
-                // Process subsequent list items
-                while i < tokens.len() {
-                    let next_line_end = tokens[i..]
-                        .iter()
-                        .position(|t| *t == Token::NewLine)
-                        .map_or(tokens.len(), |p| i + p);
-                    let next_line_tokens = &tokens[i..next_line_end];
-
-                    if next_line_tokens.is_empty() {
-                        i = next_line_end + 1;
-                        break; // Blank line ends the list
-                    }
-
-                    if let Some(Token::ListItemStart) = next_line_tokens.first() {
-                        let item_content = parse_inlines(&next_line_tokens[1..]);
-                        list_items.push(Node::ListItem(item_content));
-                        i = next_line_end + 1;
-                    } else {
-                        break; // Not a list item, so the list ends
-                    }
-                }
-                nodes.push(Node::UnorderedList(list_items));
-                continue; // Continue the main loop
-            }
-            _ => {
-                let content = parse_inlines(line_tokens);
-                nodes.push(Node::Paragraph(content));
-            }
-        }
-        i = end_of_line + 1;
+/// Converts Markdown to HTML like [`to_html_with_options`], highlighting code
+/// blocks with `highlighter` whatever [`Options::syntax_highlighting`] says.
+pub fn to_html_with_highlighter(
+    input: &str,
+    options: &Options,
+    highlighter: &dyn Highlighter,
+) -> String {
+    if options.legacy_02 {
+        return legacy::to_html(input);
     }
-    Node::Document(nodes)
+    HtmlRenderer::new(options)
+        .highlighter(highlighter)
+        .render(&parse_document_with_options(input, options))
 }
 
-// This is our powerful helper function to handle text styles.
-// It can even handle nesting, like **bold *and* italic**.
-fn parse_inlines(tokens: &[Token]) -> Vec<Node> {
-    let mut nodes = Vec::new();
-    let mut i = 0;
-    while i < tokens.len() {
-        match &tokens[i] {
-            Token::Text(text) => {
-                nodes.push(Node::Text(text.clone()));
-                i += 1;
-            }
-            Token::BoldStart => {
-                i += 1; // Consume BoldStart
-                // Find the matching BoldEnd
-                let end_pos = tokens[i..]
-                    .iter()
-                    .position(|t| matches!(t, Token::BoldEnd))
-                    .map_or(tokens.len(), |pos| i + pos);
-
-                // Recursively parse the content inside the bold tags
-                let inner_nodes = parse_inlines(&tokens[i..end_pos]);
-                nodes.push(Node::Bold(inner_nodes));
-
-                i = end_pos;
-                if i < tokens.len() {
-                    i += 1; // Consume BoldEnd
-                }
-            }
-            Token::ItalicStart => {
-                i += 1; // Consume ItalicStart
-                // Find the matching ItalicEnd
-                let end_pos = tokens[i..]
-                    .iter()
-                    .position(|t| matches!(t, Token::ItalicEnd))
-                    .map_or(tokens.len(), |pos| i + pos);
-
-                // Recursively parse the content inside the italic tags
-                let inner_nodes = parse_inlines(&tokens[i..end_pos]);
-                nodes.push(Node::Italic(inner_nodes));
-
-                i = end_pos;
-                if i < tokens.len() {
-                    i += 1; // Consume ItalicEnd
-                }
-            }
-            Token::Link { text, url } => {
-                nodes.push(Node::Link {
-                    text: text.clone(),
-                    url: url.clone(),
-                });
-                i += 1;
-            }
-            // We shouldn't encounter these here if our block parsing is correct, but we'll skip them.
-            Token::Heading(_)
-            | Token::NewLine
-            | Token::BoldEnd
-            | Token::ItalicEnd
-            | Token::ListItemStart
-            | Token::CodeBlock(_) => {
-                i += 1;
-            }
-        }
+/// Converts Markdown to HTML like [`to_html_with_options`], but refuses input
+/// with broken markup instead of rendering it as best it can. Any of the
+/// problems [`parse_with_diagnostics`] reports is an error: an unterminated
+/// code fence, unclosed emphasis, a reference link without a definition or a
+/// link missing its closing `)`.
+pub fn try_to_html(input: &str, options: &Options) -> Result<String, ParseError> {
+    if options.legacy_02 {
+        return Ok(legacy::to_html(input));
+    }
+    let (_, document, diagnostics) = split_and_parse(input, options);
+    match ParseError::from_diagnostics(diagnostics) {
+        Some(error) => Err(error),
+        None => Ok(HtmlRenderer::new(options).render(&document)),
     }
-    nodes
 }
 
-fn render(node: &Node) -> String {
-    match node {
-        Node::Document(children) => children
-            .iter()
-            .map(render)
-            .collect::<Vec<String>>()
-            .join("\n"),
-        Node::Heading(level, children) => {
-            format!("<h{}>{}</h{}>", level, render_all(children), level)
-        }
-        Node::Paragraph(children) => {
-            format!("<p>{}</p>", render_all(children))
-        }
-        Node::Bold(children) => {
-            format!("<strong>{}</strong>", render_all(children))
-        }
-        Node::Italic(children) => {
-            format!("<em>{}</em>", render_all(children))
-        }
-        Node::Text(text) => text
-            .replace("&", "&amp;")
-            .replace("<", "&lt;")
-            .replace(">", "&gt;"),
-        Node::Link { text, url } => {
-            let escaped_text = text
-                .replace("&", "&amp;")
-                .replace("<", "&lt;")
-                .replace(">", "&gt;");
-            format!("<a href=\"{}\">{}</a>", url, escaped_text)
-        }
-        Node::UnorderedList(children) => {
-            let items = children
-                .iter()
-                .map(render)
-                .collect::<Vec<String>>()
-                .join("\n");
-            format!("<ul>\n{}\n</ul>", items)
-        }
-        Node::ListItem(children) => {
-            format!("<li>{}</li>", render_all(children))
-        }
-        Node::CodeBlock(content) => {
-            let escaped_content = content;
-            format!("<pre><code>{}</code></pre>", escaped_content)
-        }
-    }
+/// Converts Markdown to HTML like [`to_html`], and also returns the document's
+/// front matter: a leading block of `key: value` lines between `---`
+/// delimiters, or of `key = value` lines between `+++` delimiters.
+/// The metadata is empty if there is no front matter.
+pub fn to_html_with_metadata(input: &str) -> (String, Metadata) {
+    let (metadata, ast, _) = split_and_parse(input, &Options::default());
+    (render(&ast), metadata)
 }
 
-fn render_all(nodes: &[Node]) -> String {
-    nodes.iter().map(render).collect()
+// Splits off the front matter and parses the rest of the document. 0.2 knew
+// nothing of front matter, spans or diagnostics, so in legacy mode there are
+// none.
+fn split_and_parse(input: &str, options: &Options) -> (Metadata, Document, Vec<Diagnostic>) {
+    if options.legacy_02 {
+        return (Metadata::new(), legacy::parse_document(input), Vec::new());
+    }
+    let (metadata, body) = split_front_matter(input).unwrap_or((Metadata::new(), input));
+    let cursor = Cursor::at(input, input.len() - body.len());
+    let Lexed {
+        tokens,
+        spans,
+        mut diagnostics,
+        ..
+    } = lex_document(cursor, options);
+    let document = parse_spanned(&tokens, &spans, &mut diagnostics);
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+    (metadata, document, diagnostics)
 }
@@ -0,0 +1,581 @@
+// Markdown output: the AST written back out as Markdown, in one canonical
+// style, so that documents can be edited as trees and saved again.
+
+use crate::ast::{Alignment, Document, Node, NodeKind};
+use crate::front_matter::split_front_matter;
+use crate::lexer::{extended_autolink, scan_autolink, starts_word};
+use crate::raw_html::{HtmlBlockEnd, html_block_start};
+use crate::{Options, split_and_parse};
+
+/// Writes a parsed document back out as Markdown, the inverse of
+/// [`parse_document`](crate::parse_document):
+///
+/// ```
+/// let document = mark_html::parse_document("## Title\n\n__bold__ and _em_");
+/// let markdown = mark_html::render_markdown(&document);
+/// assert_eq!(markdown, "## Title\n\n**bold** and *em*\n");
+/// ```
+///
+/// The output is canonical, whatever the input looked like: `#` headings,
+/// `-` bullets, ordered lists numbered `1.`, `2.` and so on from their first
+/// number, `*` and `**` emphasis, inline links, and fenced code blocks.
+/// Parsing it again gives back the same tree, spans and spaces around
+/// headings aside, and rendering that gives back the same Markdown. Text is
+/// written as it is, since the parser has no backslash escapes, so this holds
+/// for text that was text when parsed, backticks included: code spans are
+/// fenced so as not to pair up with them. The exception is a stray `*`, `_`
+/// or `~` left over from emphasis or strikethrough, such as the first `*` of
+/// `**a*`, which may pair up differently when read back.
+pub fn render_markdown(document: &Document) -> String {
+    render_markdown_with_options(document, &Options::default())
+}
+
+/// Like [`render_markdown`], for a document parsed with `options`. Links are
+/// written as autolinks where the extensions would read them back that way.
+pub fn render_markdown_with_options(document: &Document, options: &Options) -> String {
+    MarkdownRenderer::new(options, &Style::default()).render_document(document)
+}
+
+/// Reformats Markdown in a consistent [`Style`], like `rustfmt` for code:
+///
+/// ```
+/// let style = mark_html::Style::new().wrap(20);
+/// let input = "#  Notes\n\nSome _very_ long line of __text__.\n";
+/// let formatted = mark_html::format_markdown(input, &mark_html::Options::new(), &style);
+/// assert_eq!(formatted, "# Notes\n\nSome *very* long\nline of **text**.\n");
+/// ```
+///
+/// The input is parsed with `options`, so extensions that are off are
+/// formatted as the text they are read as. Front matter is kept as it is.
+/// The document is written as [`render_markdown`] writes it: headings with
+/// `#`, bullets with [`Style::bullet`], ordered lists renumbered to count up
+/// from their first number, and reference links inline. Formatting what this
+/// returns changes nothing.
+pub fn format_markdown(input: &str, options: &Options, style: &Style) -> String {
+    let (_, document, _) = split_and_parse(input, options);
+    let body = MarkdownRenderer::new(options, style).render_document(&document);
+    let front_matter = match split_front_matter(input) {
+        Some((_, rest)) if !options.legacy_02 => &input[..input.len() - rest.len()],
+        _ => "",
+    };
+    match (front_matter, body.is_empty()) {
+        ("", _) => body,
+        (_, true) => front_matter.to_string(),
+        _ => format!("{}\n{}", front_matter, body),
+    }
+}
+
+/// The choices [`format_markdown`] leaves open. The default is the style of
+/// [`render_markdown`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Style {
+    /// The character code fences are made of, `` ` `` or `~`. Backticks are
+    /// the default; tildes are used anyway when the info string has a
+    /// backtick.
+    pub fence: char,
+    /// The character list items start with, `-`, `*` or `+`. `-` is the
+    /// default.
+    pub bullet: char,
+    /// Pad table cells so that the columns line up.
+    pub align_tables: bool,
+    /// Wrap paragraphs to fit in this many columns, or leave their line
+    /// breaks alone. Lines are never broken inside code spans or link
+    /// destinations, nor where the next line would start a block.
+    pub wrap: Option<usize>,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            fence: '`',
+            bullet: '-',
+            align_tables: true,
+            wrap: None,
+        }
+    }
+}
+
+impl Style {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn fence(mut self, fence: char) -> Self {
+        self.fence = fence;
+        self
+    }
+
+    pub fn bullet(mut self, bullet: char) -> Self {
+        self.bullet = bullet;
+        self
+    }
+
+    pub fn align_tables(mut self, enabled: bool) -> Self {
+        self.align_tables = enabled;
+        self
+    }
+
+    pub fn wrap(mut self, columns: usize) -> Self {
+        self.wrap = Some(columns);
+        self
+    }
+}
+
+// Stands in for the spaces a paragraph may be wrapped at while it is being
+// rendered.
+const BREAK: &str = "\u{1f}";
+
+// Stands in for a soft break until the paragraph's lines are known, so that
+// newlines inside inline HTML aren't taken for the start of a line.
+const SOFT_BREAK: &str = "\u{1e}";
+
+#[derive(Clone, Copy)]
+struct MarkdownRenderer<'a> {
+    options: &'a Options,
+    style: &'a Style,
+    // Whether spaces in text and soft breaks are written as `BREAK`.
+    wrapping: bool,
+    // The lengths of the runs of backticks in the text of the block being
+    // rendered, which code span fences are kept clear of.
+    backticks: &'a [usize],
+}
+
+impl<'a> MarkdownRenderer<'a> {
+    fn new(options: &'a Options, style: &'a Style) -> Self {
+        MarkdownRenderer {
+            options,
+            style,
+            wrapping: false,
+            backticks: &[],
+        }
+    }
+
+    fn render_document(&self, document: &Document) -> String {
+        let markdown = self.render(document);
+        // An HTML block that is never closed takes in the rest of the input,
+        // newlines included, so nothing is added after one.
+        if markdown.is_empty() || ends_in_open_html(document) {
+            markdown
+        } else {
+            markdown + "\n"
+        }
+    }
+
+    fn render(&self, node: &Node) -> String {
+        match &node.kind {
+            NodeKind::Document(children) => children
+                .iter()
+                .map(|child| self.render(child))
+                .collect::<Vec<String>>()
+                .join("\n\n"),
+            NodeKind::Heading(level, children) => {
+                // The parser keeps spaces after the `#`s beyond the first.
+                let content = self.render_block_inlines(children);
+                let content = content.trim_matches(' ');
+                if content.is_empty() {
+                    "#".repeat(*level)
+                } else {
+                    format!("{} {}", "#".repeat(*level), content)
+                }
+            }
+            NodeKind::Paragraph(children) => match self.style.wrap {
+                Some(columns) => {
+                    let wrapping = MarkdownRenderer {
+                        wrapping: true,
+                        ..*self
+                    };
+                    wrap(&wrapping.render_block_inlines(children), columns)
+                }
+                None => indent_block_starts(&self.render_block_inlines(children)),
+            },
+            NodeKind::UnorderedList(children) => children
+                .iter()
+                .map(|child| self.render(child))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            NodeKind::OrderedList { start, children } => children
+                .iter()
+                .enumerate()
+                .map(|(i, item)| match &item.kind {
+                    NodeKind::ListItem(children) => {
+                        format!("{}. {}", start + i, self.render_block_inlines(children))
+                    }
+                    _ => self.render(item),
+                })
+                .collect::<Vec<String>>()
+                .join("\n"),
+            NodeKind::ListItem(children) => {
+                format!(
+                    "{} {}",
+                    self.style.bullet,
+                    self.render_block_inlines(children)
+                )
+            }
+            NodeKind::CodeBlock { info, content } => {
+                // Backticks can't be used if the info string has any.
+                let ch = if info.contains('`') {
+                    '~'
+                } else {
+                    self.style.fence
+                };
+                // The fence has to be longer than any run of the same
+                // character in the code that could close it.
+                let longest = content
+                    .lines()
+                    .map(|line| {
+                        line.trim_start().len() - line.trim_start().trim_start_matches(ch).len()
+                    })
+                    .max()
+                    .unwrap_or(0);
+                let fence = ch.to_string().repeat(longest.max(2) + 1);
+                let newline = if content.is_empty() || content.ends_with('\n') {
+                    ""
+                } else {
+                    "\n"
+                };
+                // An info string such as `~~x` would run into a `~~~` fence.
+                let space = if info.starts_with(ch) { " " } else { "" };
+                format!(
+                    "{}{}{}\n{}{}{}",
+                    fence, space, info, content, newline, fence
+                )
+            }
+            NodeKind::HtmlBlock(html) => html.clone(),
+            NodeKind::TableOfContents => "[[toc]]".to_string(),
+            NodeKind::Table {
+                alignments,
+                children,
+            } => self.render_table(alignments, children),
+            NodeKind::TableRow(_) => format!("| {} |", self.table_cells(node).join(" | ")),
+            NodeKind::FootnoteDefinition { label, children } => {
+                let text = indent_block_starts(&self.render_block_inlines(children));
+                format!("[^{}]: {}", label, text)
+            }
+            // Inline nodes on their own.
+            _ => self
+                .render_inlines(std::slice::from_ref(node), None)
+                .replace(SOFT_BREAK, "\n"),
+        }
+    }
+
+    fn render_table(&self, alignments: &[Alignment], rows: &[Node]) -> String {
+        let rows = rows
+            .iter()
+            .map(|row| self.table_cells(row))
+            .collect::<Vec<Vec<String>>>();
+        // Without alignment every column is as narrow as the delimiter row
+        // allows, so cells are left unpadded.
+        let widths = alignments
+            .iter()
+            .enumerate()
+            .map(|(i, _)| {
+                let widest = rows
+                    .iter()
+                    .filter_map(|row| row.get(i))
+                    .map(|cell| width(cell));
+                match self.style.align_tables {
+                    true => widest.max().unwrap_or(0).max(3),
+                    false => 3,
+                }
+            })
+            .collect::<Vec<usize>>();
+        let alignment = |i: usize| alignments.get(i).copied().unwrap_or(Alignment::None);
+        let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+        let delimiter = widths
+            .iter()
+            .enumerate()
+            .map(|(i, &width)| match alignment(i) {
+                Alignment::None => "-".repeat(width),
+                Alignment::Left => format!(":{}", "-".repeat(width - 1)),
+                Alignment::Center => format!(":{}:", "-".repeat(width - 2)),
+                Alignment::Right => format!("{}:", "-".repeat(width - 1)),
+            })
+            .collect();
+        let mut lines = rows.into_iter().map(|row| {
+            let cells = row.into_iter().enumerate().map(|(i, cell)| {
+                let Some(&width) = widths.get(i).filter(|_| self.style.align_tables) else {
+                    return cell;
+                };
+                let padding = width.saturating_sub(self::width(&cell));
+                let (left, right) = match alignment(i) {
+                    Alignment::None | Alignment::Left => (0, padding),
+                    Alignment::Center => (padding / 2, padding - padding / 2),
+                    Alignment::Right => (padding, 0),
+                };
+                format!("{}{}{}", " ".repeat(left), cell, " ".repeat(right))
+            });
+            line(cells.collect())
+        });
+        let header = lines.next().unwrap_or_default();
+        [header, line(delimiter)]
+            .into_iter()
+            .chain(lines)
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn table_cells(&self, row: &Node) -> Vec<String> {
+        let NodeKind::TableRow(cells) = &row.kind else {
+            return vec![self.render(row)];
+        };
+        cells
+            .iter()
+            .map(|cell| match &cell.kind {
+                NodeKind::TableCell(children) => {
+                    self.render_block_inlines(children).replace('|', "\\|")
+                }
+                _ => self.render(cell),
+            })
+            .collect()
+    }
+
+    // Renders the inline content of a block.
+    fn render_block_inlines(&self, nodes: &[Node]) -> String {
+        let mut backticks = Vec::new();
+        backtick_runs(nodes, &mut backticks);
+        MarkdownRenderer {
+            backticks: &backticks,
+            ..*self
+        }
+        .render_inlines(nodes, None)
+    }
+
+    // Renders inline nodes. `parent` is the delimiter of the emphasis they
+    // are directly inside, if any.
+    fn render_inlines(&self, nodes: &[Node], parent: Option<char>) -> String {
+        let mut markdown = String::new();
+        for (i, node) in nodes.iter().enumerate() {
+            let last = i + 1 == nodes.len();
+            self.render_inline(node, &mut markdown, parent.filter(|_| last));
+        }
+        markdown
+    }
+
+    // Appends an inline node to `markdown`. `closer` is the delimiter that
+    // will directly follow it, if any.
+    fn render_inline(&self, node: &Node, markdown: &mut String, closer: Option<char>) {
+        match &node.kind {
+            NodeKind::Text(text) if self.wrapping => markdown.push_str(&text.replace(' ', BREAK)),
+            NodeKind::Text(text) => markdown.push_str(text),
+            NodeKind::Bold(children) | NodeKind::Italic(children) => {
+                // `*` unless that would run into the delimiter just before or
+                // after, as nested or adjacent emphasis would.
+                let ch = if markdown.ends_with('*') || closer == Some('*') {
+                    '_'
+                } else {
+                    '*'
+                };
+                let count = if matches!(node.kind, NodeKind::Bold(_)) {
+                    2
+                } else {
+                    1
+                };
+                let delimiter = ch.to_string().repeat(count);
+                markdown.push_str(&delimiter);
+                let content = self.render_inlines(children, Some(ch));
+                markdown.push_str(&content);
+                markdown.push_str(&delimiter);
+            }
+            NodeKind::Strikethrough(children) => {
+                markdown.push_str("~~");
+                markdown.push_str(&self.render_inlines(children, None));
+                markdown.push_str("~~");
+            }
+            NodeKind::Link {
+                children,
+                url,
+                title,
+            } => {
+                if title.is_none()
+                    && let Some(autolink) = self.autolink(children, url, markdown)
+                {
+                    markdown.push_str(&autolink);
+                    return;
+                }
+                markdown.push('[');
+                markdown.push_str(&self.render_inlines(children, None));
+                markdown.push_str("](");
+                markdown.push_str(&link_destination(url));
+                if let Some(title) = title {
+                    let title = title.replace('\\', "\\\\").replace('"', "\\\"");
+                    markdown.push_str(&format!(" \"{}\"", title));
+                }
+                markdown.push(')');
+            }
+            NodeKind::Code(code) => markdown.push_str(&code_span(code, self.backticks)),
+            NodeKind::HtmlInline(html) => markdown.push_str(html),
+            NodeKind::SoftBreak if self.wrapping => markdown.push_str(BREAK),
+            NodeKind::SoftBreak => markdown.push_str(SOFT_BREAK),
+            NodeKind::TaskMarker(checked) => {
+                markdown.push_str(if *checked { "[x]" } else { "[ ]" })
+            }
+            NodeKind::FootnoteReference(label) => markdown.push_str(&format!("[^{}]", label)),
+            // Blocks where inlines belong.
+            _ => markdown.push_str(&self.render(node)),
+        }
+    }
+
+    // A link as the autolink it was parsed from, if the options would read
+    // it back as the same link: `<https://example.com>`, or a bare
+    // `www.example.com`, which otherwise would be linked again inside `[...]`.
+    fn autolink(&self, children: &[Node], url: &str, before: &str) -> Option<String> {
+        let [
+            Node {
+                kind: NodeKind::Text(text),
+                ..
+            },
+        ] = children
+        else {
+            return None;
+        };
+        if self.options.autolinks
+            && scan_autolink(&format!("{}>", text)) == Some((text.len(), url.to_string()))
+        {
+            return Some(format!("<{}>", text));
+        }
+        let bare = self.options.extended_autolinks
+            && starts_word(before.chars().next_back())
+            && extended_autolink(text) == Some((text.len(), url.to_string()));
+        bare.then(|| text.clone())
+    }
+}
+
+// Joins the words of a paragraph rendered with `BREAK`s into lines of at
+// most `columns`, where that can be done. A word that would start a block at
+// the start of a line, such as `-` or `#`, is kept on the line before it.
+fn wrap(text: &str, columns: usize) -> String {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split(BREAK).filter(|word| !word.is_empty()) {
+        if !line.is_empty() {
+            if width(&line) + 1 + width(word) > columns && !starts_block(word) {
+                lines.push(std::mem::take(&mut line));
+            } else {
+                line.push(' ');
+            }
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+    lines.join("\n")
+}
+
+// Whether a line starting with `word` could be read as something other than
+// the rest of a paragraph.
+fn starts_block(word: &str) -> bool {
+    let digits = word.trim_start_matches(|c: char| c.is_ascii_digit());
+    let ordered = digits.len() < word.len() && digits.starts_with(['.', ')']);
+    ordered
+        || word.starts_with(['#', '-', '+', '>', '|', '<', '~', '='])
+        || word.starts_with("```")
+        || word.starts_with("[[")
+        || word.starts_with("[^")
+        || word.contains("]:")
+        || word == "*"
+}
+
+// Joins the lines of a paragraph rendered with `SOFT_BREAK`s, indenting
+// those after the first that would be read as the start of a block, such as
+// `# not a heading`. The parser drops the indent again, and an indented line
+// can't start a code block there.
+fn indent_block_starts(text: &str) -> String {
+    text.split(SOFT_BREAK)
+        .enumerate()
+        .map(|(i, line)| {
+            let word = line.split(' ').next().unwrap_or_default();
+            if i > 0 && starts_block(word) {
+                format!("    {}", line)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// Whether a document ends with an HTML block whose end marker, such as the
+// `-->` of a comment, never comes.
+fn ends_in_open_html(document: &Document) -> bool {
+    let NodeKind::Document(children) = &document.kind else {
+        return false;
+    };
+    let Some(NodeKind::HtmlBlock(html)) = children.last().map(|child| &child.kind) else {
+        return false;
+    };
+    match html_block_start(html.lines().next().unwrap_or_default(), false) {
+        Some(end @ HtmlBlockEnd::Marker(_)) => !html.lines().any(|line| end.ends_on(line)),
+        _ => false,
+    }
+}
+
+// The number of columns `text` takes up, taking every character as one.
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
+
+// A link destination, in `<...>` if it is empty or has characters that would
+// end a bare one. Backslashes and the delimiters are escaped.
+fn link_destination(url: &str) -> String {
+    let escaped = url.replace('\\', "\\\\");
+    let mut depth = 0i32;
+    let balanced = url.chars().all(|c| {
+        depth += match c {
+            '(' => 1,
+            ')' => -1,
+            _ => 0,
+        };
+        depth >= 0
+    }) && depth == 0;
+    if url.is_empty()
+        || !balanced
+        || url.contains([' ', '<', '>'])
+        || url.contains(char::is_control)
+    {
+        format!("<{}>", escaped.replace('<', "\\<").replace('>', "\\>"))
+    } else {
+        escaped
+    }
+}
+
+// A code span, fenced with more backticks than any run inside it and with
+// a different number than any run in `backticks`, the text around it, which
+// would otherwise pair with the fence. It is padded with spaces if the code
+// starts or ends with something the padding would otherwise be taken from.
+fn code_span(code: &str, backticks: &[usize]) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in code.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    let length = (longest + 1..)
+        .find(|length| !backticks.contains(length))
+        .unwrap_or_default();
+    let fence = "`".repeat(length);
+    let pad = code.starts_with('`')
+        || code.ends_with('`')
+        || (code.starts_with(' ') && code.ends_with(' ') && code.trim() != "");
+    if pad {
+        format!("{} {} {}", fence, code, fence)
+    } else {
+        format!("{}{}{}", fence, code, fence)
+    }
+}
+
+// Collects the lengths of the runs of backticks in the text of `nodes`.
+fn backtick_runs(nodes: &[Node], runs: &mut Vec<usize>) {
+    for node in nodes {
+        match &node.kind {
+            NodeKind::Text(text) => runs.extend(
+                text.split(|c| c != '`')
+                    .filter(|run| !run.is_empty())
+                    .map(str::len),
+            ),
+            NodeKind::Bold(children)
+            | NodeKind::Italic(children)
+            | NodeKind::Strikethrough(children)
+            | NodeKind::Link { children, .. } => backtick_runs(children, runs),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,200 @@
+// The knobs for parsing and rendering. Every option is off by default, giving
+// the same output as `to_html`.
+
+/// Options controlling how Markdown is converted to HTML.
+///
+/// `Options::default()` gives the same output as [`to_html`](crate::to_html).
+/// The fields can be set directly, or with the builder methods of the same
+/// name:
+///
+/// ```
+/// let options = mark_html::Options::new().tables(true).strikethrough(true);
+/// assert!(options.tables);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Options {
+    /// Tables made of `|`-separated rows, with a `| --- | :-: |` row under
+    /// the header setting the alignment of each column.
+    pub tables: bool,
+    /// `~~text~~` or `~text~` for struck-through text.
+    pub strikethrough: bool,
+    /// `- [ ] item` and `- [x] item` list items, rendered with a checkbox.
+    pub task_lists: bool,
+    /// `<https://example.com>` and `<user@example.com>` as links.
+    pub autolinks: bool,
+    /// Bare `www.example.com`, `http://` and `https://` URLs in text as links.
+    pub extended_autolinks: bool,
+    /// `[^note]` references to `[^note]: text` footnotes, which are collected
+    /// into a numbered list at the end of the document.
+    pub footnotes: bool,
+
+    /// Pass HTML tags, comments and blocks found in the input through to the
+    /// output unchanged. Off by default, in which case they are escaped and
+    /// show up as text, which is the safe choice for untrusted input.
+    pub raw_html: bool,
+    /// Drop link destinations with a `javascript:`, `vbscript:`, `file:` or
+    /// `data:` scheme, leaving an empty `href`.
+    pub filter_urls: bool,
+
+    /// Give every heading an `id` attribute derived from its text, the way
+    /// GitHub does, so sections can be linked to as `#section-title`.
+    pub heading_ids: bool,
+    /// Also put a permalink, `<a class="anchor" href="#id">`, at the start of
+    /// each heading. Implies `heading_ids`.
+    pub heading_anchors: bool,
+    /// Add a `data-sourcepos="startLine:startCol-endLine:endCol"` attribute,
+    /// as cmark does, to every block element, giving the part of the input it
+    /// was rendered from. Editors use it to keep a preview scrolled in step
+    /// with the source.
+    pub source_positions: bool,
+    /// How line breaks inside a paragraph are written out.
+    pub soft_breaks: SoftBreak,
+    /// Close void elements XHTML-style, as in `<br />`, instead of `<br>`.
+    pub xhtml: bool,
+    /// Color the code in fenced code blocks for the language named in their
+    /// info string, with [`BuiltinHighlighter`](crate::BuiltinHighlighter).
+    /// The code gets a `language-*` class, and its tokens `tok-*` classes
+    /// for [`HIGHLIGHT_CSS`](crate::HIGHLIGHT_CSS) to style.
+    pub syntax_highlighting: bool,
+    /// In plain text, follow each link's text with its destination in
+    /// parentheses, as in `the docs (https://example.com/docs)`. See
+    /// [`to_plain_text_with_options`](crate::to_plain_text_with_options).
+    pub link_urls: bool,
+
+    /// Lex, parse and render exactly as version 0.2 did, quirks included, so
+    /// documents written for it keep their output. Every other option is
+    /// ignored. Set by [`Preset::Legacy02`].
+    pub legacy_02: bool,
+}
+
+/// A named set of [`Options`] for a Markdown dialect, used with
+/// [`Options::preset`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// CommonMark, which adds `<url>` autolinks to the defaults. Raw HTML is
+    /// still escaped, as cmark does unless told otherwise.
+    CommonMark,
+    /// GitHub Flavored Markdown: CommonMark plus tables, strikethrough, task
+    /// lists, bare URL autolinks and footnotes.
+    Gfm,
+    /// The output of version 0.2, byte for byte. See [`Options::legacy_02`].
+    Legacy02,
+}
+
+/// How a line break inside a paragraph is rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SoftBreak {
+    /// A newline, which browsers show as a space.
+    #[default]
+    Newline,
+    /// A space.
+    Space,
+    /// A `<br>`, keeping the lines apart the way GitHub comments do.
+    Break,
+}
+
+impl Options {
+    /// The default options, as a starting point for the builder methods.
+    pub fn new() -> Self {
+        Options::default()
+    }
+
+    /// The options for a dialect, which the builder methods can adjust:
+    ///
+    /// ```
+    /// use mark_html::{Options, Preset};
+    ///
+    /// let html = mark_html::to_html_with_options("~~old~~ new", &Options::preset(Preset::Gfm));
+    /// assert_eq!(html, "<p><del>old</del> new</p>");
+    /// ```
+    pub fn preset(preset: Preset) -> Self {
+        match preset {
+            Preset::CommonMark => Options::new().autolinks(true),
+            Preset::Gfm => Options::preset(Preset::CommonMark)
+                .tables(true)
+                .strikethrough(true)
+                .task_lists(true)
+                .extended_autolinks(true)
+                .footnotes(true),
+            Preset::Legacy02 => Options {
+                legacy_02: true,
+                ..Options::default()
+            },
+        }
+    }
+
+    pub fn tables(mut self, enabled: bool) -> Self {
+        self.tables = enabled;
+        self
+    }
+
+    pub fn strikethrough(mut self, enabled: bool) -> Self {
+        self.strikethrough = enabled;
+        self
+    }
+
+    pub fn task_lists(mut self, enabled: bool) -> Self {
+        self.task_lists = enabled;
+        self
+    }
+
+    pub fn autolinks(mut self, enabled: bool) -> Self {
+        self.autolinks = enabled;
+        self
+    }
+
+    pub fn extended_autolinks(mut self, enabled: bool) -> Self {
+        self.extended_autolinks = enabled;
+        self
+    }
+
+    pub fn footnotes(mut self, enabled: bool) -> Self {
+        self.footnotes = enabled;
+        self
+    }
+
+    pub fn raw_html(mut self, enabled: bool) -> Self {
+        self.raw_html = enabled;
+        self
+    }
+
+    pub fn filter_urls(mut self, enabled: bool) -> Self {
+        self.filter_urls = enabled;
+        self
+    }
+
+    pub fn heading_ids(mut self, enabled: bool) -> Self {
+        self.heading_ids = enabled;
+        self
+    }
+
+    pub fn heading_anchors(mut self, enabled: bool) -> Self {
+        self.heading_anchors = enabled;
+        self
+    }
+
+    pub fn source_positions(mut self, enabled: bool) -> Self {
+        self.source_positions = enabled;
+        self
+    }
+
+    pub fn soft_breaks(mut self, soft_breaks: SoftBreak) -> Self {
+        self.soft_breaks = soft_breaks;
+        self
+    }
+
+    pub fn xhtml(mut self, enabled: bool) -> Self {
+        self.xhtml = enabled;
+        self
+    }
+
+    pub fn syntax_highlighting(mut self, enabled: bool) -> Self {
+        self.syntax_highlighting = enabled;
+        self
+    }
+
+    pub fn link_urls(mut self, enabled: bool) -> Self {
+        self.link_urls = enabled;
+        self
+    }
+}
@@ -0,0 +1,173 @@
+// Standalone HTML pages: the rendered document wrapped in `<!DOCTYPE html>`,
+// a `<head>` and a `<body>` laid out by a template.
+
+use crate::ast::{Document, NodeKind, plain_text};
+use crate::html::{HtmlRenderer, escape_attribute, escape_html};
+use crate::toc::{render_toc, table_of_contents};
+use crate::{HIGHLIGHT_CSS, Metadata, Options, legacy, split_and_parse};
+
+/// Settings for [`to_html_page`]. Everything is optional; by default the
+/// title and description come from the document.
+///
+/// ```
+/// let page = mark_html::Page::new()
+///     .stylesheet("style.css")
+///     .template("<main>{{content}}</main>");
+/// let html = mark_html::to_html_page("# Notes", &mark_html::Options::new(), &page);
+/// assert!(html.contains("<title>Notes</title>"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Page {
+    /// The `<title>`, instead of the front matter `title` or the text of the
+    /// first heading.
+    pub title: Option<String>,
+    /// The `<meta name="description">`, instead of the front matter
+    /// `description`. Left out if neither is set.
+    pub description: Option<String>,
+    /// URLs of stylesheets to link to, in order.
+    pub stylesheets: Vec<String>,
+    /// The contents of `<body>`, with `{{title}}`, `{{content}}` and `{{toc}}`
+    /// placeholders for the title, the rendered document and a table of
+    /// contents of its headings. Defaults to just `{{content}}`.
+    pub template: Option<String>,
+}
+
+impl Page {
+    pub fn new() -> Self {
+        Page::default()
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn stylesheet(mut self, url: impl Into<String>) -> Self {
+        self.stylesheets.push(url.into());
+        self
+    }
+
+    pub fn template(mut self, template: impl Into<String>) -> Self {
+        self.template = Some(template.into());
+        self
+    }
+}
+
+/// Converts Markdown to a complete HTML page rather than a fragment: the
+/// output of [`to_html_with_options`](crate::to_html_with_options) laid out
+/// by the [`Page`] template, with a `<head>` giving the charset, title,
+/// description and stylesheets.
+pub fn to_html_page(input: &str, options: &Options, page: &Page) -> String {
+    let (metadata, document, _) = split_and_parse(input, options);
+    render_page(input, &metadata, &document, options, page)
+}
+
+// Renders the page for `document`, as parsed from `input`, which is only
+// needed for `Options::legacy_02`.
+pub(crate) fn render_page(
+    input: &str,
+    metadata: &Metadata,
+    document: &Document,
+    options: &Options,
+    page: &Page,
+) -> String {
+    let template = page.template.as_deref().unwrap_or("{{content}}");
+    // The table of contents links to the headings by id.
+    let mut options = options.clone();
+    options.heading_ids |= template.contains("{{toc}}");
+    let content = if options.legacy_02 {
+        legacy::to_html(input)
+    } else {
+        HtmlRenderer::new(&options).render(document)
+    };
+
+    let title = page
+        .title
+        .clone()
+        .or_else(|| front_matter(metadata, "title"))
+        .or_else(|| first_heading(document))
+        .unwrap_or_default();
+    let description = page
+        .description
+        .clone()
+        .or_else(|| front_matter(metadata, "description"));
+
+    let void = if options.xhtml { " />" } else { ">" };
+    let mut head = vec![
+        format!("<meta charset=\"utf-8\"{}", void),
+        format!(
+            "<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\"{}",
+            void
+        ),
+        format!("<title>{}</title>", escape_html(&title)),
+    ];
+    if let Some(description) = description {
+        head.push(format!(
+            "<meta name=\"description\" content=\"{}\"{}",
+            escape_attribute(&description),
+            void
+        ));
+    }
+    if options.syntax_highlighting && !options.legacy_02 {
+        head.push(format!("<style>\n{}</style>", HIGHLIGHT_CSS));
+    }
+    for url in &page.stylesheets {
+        head.push(format!(
+            "<link rel=\"stylesheet\" href=\"{}\"{}",
+            escape_attribute(url),
+            void
+        ));
+    }
+
+    let body = fill_template(template, |name| match name {
+        "title" => Some(escape_html(&title)),
+        "content" => Some(content.clone()),
+        "toc" => Some(render_toc(&table_of_contents(document, 1, 6))),
+        _ => None,
+    });
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n{}\n</head>\n<body>\n{}\n</body>\n</html>\n",
+        head.join("\n"),
+        body
+    )
+}
+
+// Replaces each `{{name}}` in `template` with `value(name)`, leaving unknown
+// placeholders as they are. Values are not searched for placeholders in turn.
+pub(crate) fn fill_template(template: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        let placeholder = &rest[start..start + end + 2];
+        filled.push_str(&rest[..start]);
+        match value(&placeholder[2..placeholder.len() - 2]) {
+            Some(value) => filled.push_str(&value),
+            None => filled.push_str(placeholder),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+fn front_matter(metadata: &Metadata, key: &str) -> Option<String> {
+    metadata.get(key)?.as_str().map(str::to_string)
+}
+
+fn first_heading(document: &Document) -> Option<String> {
+    let NodeKind::Document(children) = &document.kind else {
+        return None;
+    };
+    children.iter().find_map(|child| match &child.kind {
+        NodeKind::Heading(_, children) => Some(plain_text(children).trim().to_string()),
+        _ => None,
+    })
+}
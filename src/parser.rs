@@ -0,0 +1,472 @@
+// Groups the lexer's tokens into blocks and resolves inline structure,
+// building the AST. Each node's span covers the tokens it was built from.
+
+use crate::ast::{Node, NodeKind};
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::lexer::{Table, Token};
+use crate::span::Span;
+use std::collections::HashMap;
+use std::mem::discriminant;
+
+// Parses tokens written by hand, which have no spans, so neither do the nodes.
+#[cfg(test)]
+pub(crate) fn parse(tokens: &[Token]) -> Node {
+    fn clear_spans(node: &mut Node) {
+        node.span = Span::default();
+        if let Some(children) = node.children_mut() {
+            children.iter_mut().for_each(clear_spans)
+        }
+    }
+    let spans = vec![Span::default(); tokens.len()];
+    let mut document = parse_spanned(tokens, &spans, &mut Vec::new());
+    clear_spans(&mut document);
+    document
+}
+
+// Parses `tokens`, where `spans[i]` is the span of `tokens[i]`. Problems
+// found along the way are added to `diagnostics`.
+pub(crate) fn parse_spanned(
+    tokens: &[Token],
+    spans: &[Span],
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Node {
+    let mut nodes = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        match &tokens[i] {
+            Token::CodeBlock { info, code } => {
+                let kind = NodeKind::CodeBlock {
+                    info: info.clone(),
+                    content: code.clone(),
+                };
+                nodes.push(Node::spanned(kind, spans[i]));
+                i += 1;
+                continue;
+            }
+            Token::HtmlBlock(html) => {
+                nodes.push(Node::spanned(NodeKind::HtmlBlock(html.clone()), spans[i]));
+                i += 1;
+                continue;
+            }
+            Token::Table(table) => {
+                nodes.push(parse_table(table, spans[i], diagnostics));
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        let mut end_of_line = line_end(tokens, i);
+        if end_of_line == i {
+            i += 1;
+            continue;
+        }
+
+        match &tokens[i] {
+            Token::Heading(level) => {
+                let content = parse_inlines(
+                    &tokens[i + 1..end_of_line],
+                    &spans[i + 1..end_of_line],
+                    diagnostics,
+                );
+                let span = span_of(&spans[i..end_of_line]);
+                nodes.push(Node::spanned(NodeKind::Heading(*level, content), span));
+            }
+            marker @ (Token::ListItemStart | Token::OrderedItemStart(_)) => {
+                // Each following line that starts with the same kind of list
+                // marker is another item. Anything else, a blank line
+                // included, ends the list.
+                let same_kind = |token: &Token| discriminant(token) == discriminant(marker);
+                let mut items = Vec::new();
+                while tokens.get(i).is_some_and(same_kind) {
+                    let end = line_end(tokens, i);
+                    let content =
+                        parse_inlines(&tokens[i + 1..end], &spans[i + 1..end], diagnostics);
+                    let span = span_of(&spans[i..end]);
+                    items.push(Node::spanned(NodeKind::ListItem(content), span));
+                    i = end + 1;
+                }
+                let span = span_of(&items.iter().map(|item| item.span).collect::<Vec<_>>());
+                let kind = match marker {
+                    Token::OrderedItemStart(start) => NodeKind::OrderedList {
+                        start: *start,
+                        children: items,
+                    },
+                    _ => NodeKind::UnorderedList(items),
+                };
+                nodes.push(Node::spanned(kind, span));
+                continue;
+            }
+            _ => {
+                // A paragraph runs until a blank line or the start of another block.
+                while end_of_line < tokens.len() {
+                    match tokens.get(end_of_line + 1) {
+                        None
+                        | Some(
+                            Token::NewLine
+                            | Token::Heading(_)
+                            | Token::ListItemStart
+                            | Token::OrderedItemStart(_)
+                            | Token::CodeBlock { .. }
+                            | Token::HtmlBlock(_)
+                            | Token::Table(_)
+                            | Token::FootnoteDefinition(_),
+                        ) => break,
+                        Some(_) => end_of_line = line_end(tokens, end_of_line + 1),
+                    }
+                }
+                let span = span_of(&spans[i..end_of_line]);
+                // A footnote's text is laid out like a paragraph.
+                if let Token::FootnoteDefinition(label) = &tokens[i] {
+                    let children = parse_inlines(
+                        &tokens[i + 1..end_of_line],
+                        &spans[i + 1..end_of_line],
+                        diagnostics,
+                    );
+                    let label = label.clone();
+                    nodes.push(Node::spanned(
+                        NodeKind::FootnoteDefinition { label, children },
+                        span,
+                    ));
+                    i = end_of_line + 1;
+                    continue;
+                }
+                let content =
+                    parse_inlines(&tokens[i..end_of_line], &spans[i..end_of_line], diagnostics);
+                let kind = match content.as_slice() {
+                    [
+                        Node {
+                            kind: NodeKind::Text(text),
+                            ..
+                        },
+                    ] if text.trim() == "[[toc]]" => NodeKind::TableOfContents,
+                    _ => NodeKind::Paragraph(content),
+                };
+                nodes.push(Node::spanned(kind, span));
+            }
+        }
+        i = end_of_line + 1;
+    }
+    Node::spanned(NodeKind::Document(nodes), span_of(spans))
+}
+
+fn parse_table(table: &Table, span: Span, diagnostics: &mut Vec<Diagnostic>) -> Node {
+    let rows = table
+        .rows
+        .iter()
+        .map(|row| {
+            let cells = row
+                .cells
+                .iter()
+                .map(|cell| {
+                    let content = parse_inlines(&cell.tokens, &cell.spans, diagnostics);
+                    Node::spanned(NodeKind::TableCell(content), cell.span)
+                })
+                .collect();
+            Node::spanned(NodeKind::TableRow(cells), row.span)
+        })
+        .collect();
+    Node::spanned(
+        NodeKind::Table {
+            alignments: table.alignments.clone(),
+            children: rows,
+        },
+        span,
+    )
+}
+
+// Index of the `NewLine` that ends the line starting at `start`, or the end
+// of the tokens for the last line.
+fn line_end(tokens: &[Token], start: usize) -> usize {
+    tokens[start..]
+        .iter()
+        .position(|t| *t == Token::NewLine)
+        .map_or(tokens.len(), |p| start + p)
+}
+
+// The span from the start of the first span to the end of the last one.
+fn span_of(spans: &[Span]) -> Span {
+    match (spans.first(), spans.last()) {
+        (Some(first), Some(last)) => first.to(*last),
+        _ => Span::default(),
+    }
+}
+
+// An inline element waiting for emphasis resolution: either a finished node
+// or a delimiter run that may still be matched.
+enum Inline {
+    Node(Node),
+    Delimiter(DelimiterRun),
+}
+
+struct DelimiterRun {
+    ch: char,
+    // Remaining characters that have not been used up by a match yet.
+    count: usize,
+    // Length of the run as written, needed for the "rule of 3".
+    original: usize,
+    can_open: bool,
+    can_close: bool,
+    // Shrinks along with `count`, to cover the remaining characters.
+    span: Span,
+    // The emphasis this run opens, in the order it was matched, which is
+    // from the innermost out: the characters used and the emphasis's span.
+    opens: Vec<(usize, Span)>,
+    // How many of the emphasis opened before this run it closes.
+    closes: usize,
+}
+
+impl Inline {
+    // Unmatched delimiters are just literal text. One that could only have
+    // opened emphasis was most likely meant to, and gets a diagnostic.
+    fn into_node(self, diagnostics: &mut Vec<Diagnostic>) -> Node {
+        match self {
+            Inline::Node(node) => node,
+            Inline::Delimiter(run) => {
+                let text = run.ch.to_string().repeat(run.count);
+                if run.can_open && !run.can_close {
+                    diagnostics.push(Diagnostic::new(
+                        Severity::Warning,
+                        run.span,
+                        "unclosed-emphasis",
+                        format!("`{}` is never closed, so it shows up as text", text),
+                    ));
+                }
+                Node::spanned(NodeKind::Text(text), run.span)
+            }
+        }
+    }
+}
+
+// This is our powerful helper function to handle text styles.
+// Emphasis is resolved with the CommonMark delimiter stack algorithm, so it can
+// handle nesting like **bold *and* italic** and ***both***.
+fn parse_inlines(tokens: &[Token], spans: &[Span], diagnostics: &mut Vec<Diagnostic>) -> Vec<Node> {
+    let mut items = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let span = spans[i];
+        let node = |kind| Inline::Node(Node::spanned(kind, span));
+        match &tokens[i] {
+            Token::Text(text) => items.push(node(NodeKind::Text(text.clone()))),
+            Token::Delimiter {
+                ch,
+                count,
+                can_open,
+                can_close,
+            } => items.push(Inline::Delimiter(DelimiterRun {
+                ch: *ch,
+                count: *count,
+                original: *count,
+                can_open: *can_open,
+                can_close: *can_close,
+                span,
+                opens: Vec::new(),
+                closes: 0,
+            })),
+            Token::LinkStart => {
+                // Links don't nest, so the next `LinkEnd` belongs to this link.
+                let end = (i..tokens.len())
+                    .find(|&j| matches!(tokens[j], Token::LinkEnd { .. }))
+                    .unwrap_or(tokens.len());
+                if let Some(Token::LinkEnd { url, title }) = tokens.get(end) {
+                    let children =
+                        parse_inlines(&tokens[i + 1..end], &spans[i + 1..end], diagnostics);
+                    items.push(Inline::Node(Node::spanned(
+                        NodeKind::Link {
+                            children,
+                            url: url.clone(),
+                            title: title.clone(),
+                        },
+                        span.to(spans[end]),
+                    )));
+                }
+                i = end;
+            }
+            Token::CodeSpan(code) => items.push(node(NodeKind::Code(code.clone()))),
+            Token::TaskMarker(checked) => items.push(node(NodeKind::TaskMarker(*checked))),
+            Token::FootnoteRef(label) => {
+                items.push(node(NodeKind::FootnoteReference(label.clone())))
+            }
+            Token::HtmlInline(html) => items.push(node(NodeKind::HtmlInline(html.clone()))),
+            // Lines within a paragraph are joined by soft breaks.
+            Token::NewLine => items.push(node(NodeKind::SoftBreak)),
+            // We shouldn't encounter these here if our block parsing is correct, but we'll skip them.
+            Token::Heading(_)
+            | Token::LinkEnd { .. }
+            | Token::ListItemStart
+            | Token::OrderedItemStart(_)
+            | Token::CodeBlock { .. }
+            | Token::HtmlBlock(_)
+            | Token::Table(_)
+            | Token::FootnoteDefinition(_) => {}
+        }
+        i += 1;
+    }
+    merge_text(resolve_emphasis(items, diagnostics))
+}
+
+// Matches up delimiter runs with the CommonMark delimiter stack algorithm,
+// recording the emphasis each run opens and closes, then builds the nodes.
+// `items` is left in place, and the stack is only ever searched above the
+// lowest opener each kind of closer could still match, so this is linear in
+// the number of runs.
+fn resolve_emphasis(mut items: Vec<Inline>, diagnostics: &mut Vec<Diagnostic>) -> Vec<Node> {
+    // The indices in `items` of runs that may still open emphasis.
+    let mut stack: Vec<usize> = Vec::new();
+    // For each kind of closer, the part of the stack below which there is
+    // nothing it can match.
+    let mut openers_bottom: HashMap<(char, bool, usize), usize> = HashMap::new();
+    for closer in 0..items.len() {
+        let Inline::Delimiter(run) = &items[closer] else {
+            continue;
+        };
+        let (ch, can_open, original) = (run.ch, run.can_open, run.original);
+        while run_count(&items[closer]) > 0 && can_close(&items[closer]) {
+            let key = (ch, can_open, original % 3);
+            let bottom = openers_bottom.get(&key).copied().unwrap_or(0);
+            // Look back for the nearest run of the same character that can
+            // open. Strikethrough takes runs of the same length instead of
+            // the rule of 3.
+            let found = (bottom..stack.len()).rev().find(|&p| {
+                let (Inline::Delimiter(candidate), Inline::Delimiter(run)) =
+                    (&items[stack[p]], &items[closer])
+                else {
+                    return false;
+                };
+                candidate.ch == run.ch
+                    && if run.ch == '~' {
+                        candidate.count == run.count
+                    } else {
+                        !violates_rule_of_three(candidate, run)
+                    }
+            });
+            let Some(p) = found else {
+                openers_bottom.insert(key, stack.len());
+                break;
+            };
+            let opener = stack[p];
+            // Any runs in between can no longer match and turn into text.
+            stack.truncate(p + 1);
+
+            let (before, after) = items.split_at_mut(closer);
+            let (Inline::Delimiter(open), Inline::Delimiter(close)) =
+                (&mut before[opener], &mut after[0])
+            else {
+                unreachable!("opener and closer are delimiter runs");
+            };
+            let used = if open.ch == '~' {
+                open.count
+            } else if open.count >= 2 && close.count >= 2 {
+                2
+            } else {
+                1
+            };
+            // The delimiters used up are the ones closest to the content.
+            let span = Span {
+                start: open.span.start.advance(open.count - used),
+                end: close.span.start.advance(used),
+            };
+            open.opens.push((used, span));
+            open.count -= used;
+            open.span.end = span.start;
+            close.closes += 1;
+            close.count -= used;
+            close.span.start = span.end;
+            if open.count == 0 {
+                stack.pop();
+            }
+            for bottom in openers_bottom.values_mut() {
+                *bottom = (*bottom).min(stack.len());
+            }
+        }
+        if run_count(&items[closer]) > 0 && can_open {
+            stack.push(closer);
+        }
+    }
+
+    // Emphasis still open, innermost last, with the nodes inside it so far.
+    let mut open: Vec<(char, usize, Span, Vec<Node>)> = Vec::new();
+    let mut nodes = Vec::new();
+    for item in items {
+        let run = match item {
+            Inline::Node(node) => {
+                open.last_mut()
+                    .map_or(&mut nodes, |(.., children)| children)
+                    .push(node);
+                continue;
+            }
+            Inline::Delimiter(run) => run,
+        };
+        for _ in 0..run.closes {
+            let (ch, used, span, children) = open.pop().expect("closed emphasis was opened");
+            let kind = match (ch, used) {
+                ('~', _) => NodeKind::Strikethrough(children),
+                (_, 2) => NodeKind::Bold(children),
+                _ => NodeKind::Italic(children),
+            };
+            let node = Node::spanned(kind, span);
+            open.last_mut()
+                .map_or(&mut nodes, |(.., children)| children)
+                .push(node);
+        }
+        let opens = run
+            .opens
+            .iter()
+            .rev()
+            .map(|&(used, span)| (run.ch, used, span));
+        let opens = opens.collect::<Vec<_>>();
+        if run.count > 0 {
+            let text = Inline::Delimiter(run).into_node(diagnostics);
+            open.last_mut()
+                .map_or(&mut nodes, |(.., children)| children)
+                .push(text);
+        }
+        open.extend(
+            opens
+                .into_iter()
+                .map(|(ch, used, span)| (ch, used, span, Vec::new())),
+        );
+    }
+    nodes
+}
+
+fn run_count(item: &Inline) -> usize {
+    match item {
+        Inline::Delimiter(run) => run.count,
+        Inline::Node(_) => 0,
+    }
+}
+
+fn can_close(item: &Inline) -> bool {
+    matches!(item, Inline::Delimiter(run) if run.can_close)
+}
+
+// If one of the runs can both open and close, the sum of their lengths must
+// not be a multiple of 3 unless both lengths are.
+fn violates_rule_of_three(opener: &DelimiterRun, closer: &DelimiterRun) -> bool {
+    (opener.can_close || closer.can_open)
+        && (opener.original + closer.original).is_multiple_of(3)
+        && !(opener.original.is_multiple_of(3) && closer.original.is_multiple_of(3))
+}
+
+// Joins adjacent text nodes, which the lexer and leftover delimiters tend to split up.
+fn merge_text(nodes: Vec<Node>) -> Vec<Node> {
+    let mut merged: Vec<Node> = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        if let (
+            Some(Node {
+                kind: NodeKind::Text(last),
+                span,
+            }),
+            NodeKind::Text(text),
+        ) = (merged.last_mut(), &node.kind)
+        {
+            last.push_str(text);
+            *span = span.to(node.span);
+        } else {
+            merged.push(node);
+        }
+    }
+    merged
+}
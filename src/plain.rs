@@ -0,0 +1,135 @@
+// Plain text output: the words of a document with the markup taken out, for
+// search indexes and previews that can't show HTML.
+
+use crate::ast::{Node, NodeKind};
+use crate::{Options, split_and_parse};
+
+/// Converts Markdown to plain text, with all formatting removed:
+///
+/// ```
+/// let text = mark_html::to_plain_text("# Hello\n\nSome **bold** [link](https://example.com).");
+/// assert_eq!(text, "Hello\n\nSome bold link.");
+/// ```
+///
+/// Blocks are separated by blank lines, list items start with `- ` or their
+/// number, and code blocks are kept verbatim. Links are reduced to their text, and raw HTML
+/// to the text between its tags. Front matter is left out.
+pub fn to_plain_text(input: &str) -> String {
+    to_plain_text_with_options(input, &Options::default())
+}
+
+/// Converts Markdown to plain text like [`to_plain_text`], with the given
+/// [`Options`]. With [`Options::link_urls`], each link's destination follows
+/// its text in parentheses.
+pub fn to_plain_text_with_options(input: &str, options: &Options) -> String {
+    let (_, document, _) = split_and_parse(input, options);
+    PlainTextRenderer { options }.render(&document)
+}
+
+struct PlainTextRenderer<'a> {
+    options: &'a Options,
+}
+
+impl PlainTextRenderer<'_> {
+    fn render(&self, node: &Node) -> String {
+        match &node.kind {
+            NodeKind::Document(children) => self.render_blocks(children, "\n\n"),
+            NodeKind::Heading(_, children) | NodeKind::Paragraph(children) => {
+                self.render_inlines(children)
+            }
+            NodeKind::UnorderedList(children) => self.render_blocks(children, "\n"),
+            NodeKind::OrderedList { start, children } => children
+                .iter()
+                .enumerate()
+                .map(|(i, item)| match &item.kind {
+                    NodeKind::ListItem(children) => {
+                        format!("{}. {}", start + i, self.render_inlines(children))
+                    }
+                    _ => self.render(item),
+                })
+                .collect::<Vec<String>>()
+                .join("\n"),
+            NodeKind::ListItem(children) => format!("- {}", self.render_inlines(children)),
+            NodeKind::CodeBlock { content, .. } => content.trim_end_matches('\n').to_string(),
+            NodeKind::HtmlBlock(html) => strip_tags(html).trim().to_string(),
+            NodeKind::Table { children, .. } => self.render_blocks(children, "\n"),
+            NodeKind::TableRow(cells) => cells
+                .iter()
+                .map(|cell| self.render(cell))
+                .collect::<Vec<String>>()
+                .join("\t"),
+            NodeKind::TableCell(children) => self.render_inlines(children),
+            NodeKind::FootnoteDefinition { label, children } => {
+                format!("[{}] {}", label, self.render_inlines(children))
+            }
+            _ => self.render_inlines(std::slice::from_ref(node)),
+        }
+    }
+
+    // Renders blocks joined by `separator`, leaving out any that have no
+    // text, such as a `[[toc]]` marker.
+    fn render_blocks(&self, nodes: &[Node], separator: &str) -> String {
+        nodes
+            .iter()
+            .map(|node| self.render(node))
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<String>>()
+            .join(separator)
+    }
+
+    fn render_inlines(&self, nodes: &[Node]) -> String {
+        let mut text = String::new();
+        for node in nodes {
+            match &node.kind {
+                NodeKind::Text(t) | NodeKind::Code(t) => text.push_str(t),
+                NodeKind::SoftBreak => text.push('\n'),
+                NodeKind::Bold(children)
+                | NodeKind::Italic(children)
+                | NodeKind::Strikethrough(children) => {
+                    text.push_str(&self.render_inlines(children))
+                }
+                NodeKind::Link { children, url, .. } => {
+                    let link_text = self.render_inlines(children);
+                    text.push_str(&link_text);
+                    if self.options.link_urls && !shows_url(&link_text, url) {
+                        text.push_str(&format!(" ({})", url));
+                    }
+                }
+                NodeKind::TaskMarker(checked) => {
+                    text.push_str(if *checked { "[x]" } else { "[ ]" })
+                }
+                NodeKind::FootnoteReference(label) => text.push_str(&format!("[{}]", label)),
+                NodeKind::HtmlInline(_) | NodeKind::TableOfContents => {}
+                _ => text.push_str(&self.render(node)),
+            }
+        }
+        text
+    }
+}
+
+// Whether a link's text is its destination already, as for autolinks.
+pub(crate) fn shows_url(text: &str, url: &str) -> bool {
+    [url, url.trim_start_matches("mailto:")].contains(&text)
+        || url.strip_prefix("http://") == Some(text)
+}
+
+// The text of some HTML, without its tags and comments.
+pub(crate) fn strip_tags(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let end = if rest[start..].starts_with("<!--") {
+            rest[start..].find("-->").map(|end| end + 3)
+        } else {
+            rest[start..].find('>').map(|end| end + 1)
+        };
+        let Some(end) = end else {
+            rest = &rest[start..];
+            break;
+        };
+        rest = &rest[start + end..];
+    }
+    text.push_str(rest);
+    text
+}
@@ -0,0 +1,323 @@
+// Recognition of raw HTML, following the CommonMark rules for HTML blocks
+// and inline HTML. Whether it is passed through or escaped is up to the
+// renderer, see `Options::raw_html`.
+
+use crate::span::Cursor;
+
+// Tags that start a block of kind 1. Their content may contain blank lines.
+const VERBATIM_TAGS: [&str; 4] = ["pre", "script", "style", "textarea"];
+
+// Tags that start a block of kind 6.
+const BLOCK_TAGS: [&str; 62] = [
+    "address",
+    "article",
+    "aside",
+    "base",
+    "basefont",
+    "blockquote",
+    "body",
+    "caption",
+    "center",
+    "col",
+    "colgroup",
+    "dd",
+    "details",
+    "dialog",
+    "dir",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "frame",
+    "frameset",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hr",
+    "html",
+    "iframe",
+    "legend",
+    "li",
+    "link",
+    "main",
+    "menu",
+    "menuitem",
+    "nav",
+    "noframes",
+    "ol",
+    "optgroup",
+    "option",
+    "p",
+    "param",
+    "search",
+    "section",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "track",
+    "ul",
+];
+
+// How an HTML block that has been started comes to an end.
+#[derive(Debug, PartialEq)]
+pub(crate) enum HtmlBlockEnd {
+    // The block ends with the first line containing one of these markers,
+    // compared case-insensitively. That line is part of the block.
+    Marker(&'static [&'static str]),
+    // The block ends before the next blank line.
+    BlankLine,
+}
+
+impl HtmlBlockEnd {
+    pub(crate) fn ends_on(&self, line: &str) -> bool {
+        match self {
+            HtmlBlockEnd::Marker(markers) => {
+                let line = line.to_ascii_lowercase();
+                markers.iter().any(|marker| line.contains(marker))
+            }
+            HtmlBlockEnd::BlankLine => false,
+        }
+    }
+}
+
+// Checks whether `line` opens one of the seven kinds of HTML block. The last
+// kind, a lone complete tag, may not interrupt a paragraph.
+pub(crate) fn html_block_start(line: &str, in_paragraph: bool) -> Option<HtmlBlockEnd> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let line = &line[indent..];
+    let rest = line.strip_prefix('<')?;
+
+    if let Some(name) = tag_name_prefix(rest)
+        && VERBATIM_TAGS.contains(&name.to_ascii_lowercase().as_str())
+        && ends_tag_name(&rest[name.len()..], false)
+    {
+        return Some(HtmlBlockEnd::Marker(&[
+            "</pre>",
+            "</script>",
+            "</style>",
+            "</textarea>",
+        ]));
+    }
+    if rest.starts_with("!--") {
+        return Some(HtmlBlockEnd::Marker(&["-->"]));
+    }
+    if rest.starts_with('?') {
+        return Some(HtmlBlockEnd::Marker(&["?>"]));
+    }
+    if rest.starts_with("![CDATA[") {
+        return Some(HtmlBlockEnd::Marker(&["]]>"]));
+    }
+    if rest
+        .strip_prefix('!')
+        .is_some_and(|r| r.starts_with(|c: char| c.is_ascii_alphabetic()))
+    {
+        return Some(HtmlBlockEnd::Marker(&[">"]));
+    }
+
+    let closing = rest.strip_prefix('/');
+    if let Some(name) = tag_name_prefix(closing.unwrap_or(rest)) {
+        let after = &closing.unwrap_or(rest)[name.len()..];
+        if BLOCK_TAGS.contains(&name.to_ascii_lowercase().as_str()) && ends_tag_name(after, true) {
+            return Some(HtmlBlockEnd::BlankLine);
+        }
+        if !in_paragraph && !VERBATIM_TAGS.contains(&name.to_ascii_lowercase().as_str()) {
+            let mut chars = Cursor::new(rest);
+            let tag = scan_inline_html(&mut chars)?;
+            let is_tag = !tag.starts_with("<!") && !tag.starts_with("<?");
+            if is_tag && chars.all(char::is_whitespace) {
+                return Some(HtmlBlockEnd::BlankLine);
+            }
+        }
+    }
+    None
+}
+
+fn tag_name_prefix(text: &str) -> Option<&str> {
+    if !text.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let len = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+        .unwrap_or(text.len());
+    Some(&text[..len])
+}
+
+// A block-starting tag name must be followed by whitespace, `>`, the end of
+// the line or, for kind 6, `/>`.
+fn ends_tag_name(after: &str, allow_self_closing: bool) -> bool {
+    after.is_empty()
+        || after.starts_with(char::is_whitespace)
+        || after.starts_with('>')
+        || (allow_self_closing && after.starts_with("/>"))
+}
+
+// Scans an inline HTML tag, comment, processing instruction, declaration or
+// CDATA section after its opening `<`. Returns the raw text including the `<`.
+pub(crate) fn scan_inline_html(chars: &mut Cursor) -> Option<String> {
+    let mut html = String::from("<");
+    match *chars.peek()? {
+        '!' => {
+            push_next(chars, &mut html);
+            if eat(chars, &mut html, "--") {
+                // `<!-->` and `<!--->` are complete (empty) comments.
+                if eat(chars, &mut html, ">") || eat(chars, &mut html, "->") {
+                    return Some(html);
+                }
+                take_until(chars, &mut html, "-->")?;
+            } else if eat(chars, &mut html, "[CDATA[") {
+                take_until(chars, &mut html, "]]>")?;
+            } else if chars.peek()?.is_ascii_alphabetic() {
+                take_until(chars, &mut html, ">")?;
+            } else {
+                return None;
+            }
+        }
+        '?' => {
+            push_next(chars, &mut html);
+            take_until(chars, &mut html, "?>")?;
+        }
+        '/' => {
+            push_next(chars, &mut html);
+            scan_tag_name(chars, &mut html)?;
+            eat_whitespace(chars, &mut html);
+            if !eat(chars, &mut html, ">") {
+                return None;
+            }
+        }
+        c if c.is_ascii_alphabetic() => scan_open_tag(chars, &mut html)?,
+        _ => return None,
+    }
+    Some(html)
+}
+
+fn scan_open_tag(chars: &mut Cursor, html: &mut String) -> Option<()> {
+    scan_tag_name(chars, html)?;
+    loop {
+        let had_space = eat_whitespace(chars, html);
+        match *chars.peek()? {
+            '>' => {
+                push_next(chars, html);
+                return Some(());
+            }
+            '/' => {
+                push_next(chars, html);
+                return eat(chars, html, ">").then_some(());
+            }
+            c if had_space && (c.is_ascii_alphabetic() || c == '_' || c == ':') => {
+                scan_attribute(chars, html)?;
+            }
+            _ => return None,
+        }
+    }
+}
+
+fn scan_tag_name(chars: &mut Cursor, html: &mut String) -> Option<()> {
+    if !chars.peek()?.is_ascii_alphabetic() {
+        return None;
+    }
+    while chars
+        .peek()
+        .is_some_and(|c| c.is_ascii_alphanumeric() || *c == '-')
+    {
+        push_next(chars, html);
+    }
+    Some(())
+}
+
+fn scan_attribute(chars: &mut Cursor, html: &mut String) -> Option<()> {
+    while chars
+        .peek()
+        .is_some_and(|c| c.is_ascii_alphanumeric() || "_.:-".contains(*c))
+    {
+        push_next(chars, html);
+    }
+
+    // The value is optional, so only commit to the whitespace around `=` if
+    // there is one.
+    let mut lookahead = chars.clone();
+    let mut value = String::new();
+    eat_whitespace(&mut lookahead, &mut value);
+    if !eat(&mut lookahead, &mut value, "=") {
+        return Some(());
+    }
+    eat_whitespace(&mut lookahead, &mut value);
+    match *lookahead.peek()? {
+        quote @ ('"' | '\'') => {
+            push_next(&mut lookahead, &mut value);
+            loop {
+                let c = lookahead.next()?;
+                value.push(c);
+                if c == quote {
+                    break;
+                }
+            }
+        }
+        _ => {
+            while lookahead
+                .peek()
+                .is_some_and(|c| !c.is_whitespace() && !"\"'=<>`".contains(*c))
+            {
+                push_next(&mut lookahead, &mut value);
+            }
+            if value.ends_with('=') {
+                return None;
+            }
+        }
+    }
+    *chars = lookahead;
+    html.push_str(&value);
+    Some(())
+}
+
+fn push_next(chars: &mut Cursor, html: &mut String) {
+    if let Some(c) = chars.next() {
+        html.push(c);
+    }
+}
+
+// Consumes `expected` if the input continues with it.
+fn eat(chars: &mut Cursor, html: &mut String, expected: &str) -> bool {
+    let mut lookahead = chars.clone();
+    if expected.chars().all(|c| lookahead.next() == Some(c)) {
+        *chars = lookahead;
+        html.push_str(expected);
+        true
+    } else {
+        false
+    }
+}
+
+fn eat_whitespace(chars: &mut Cursor, html: &mut String) -> bool {
+    let mut eaten = false;
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        push_next(chars, html);
+        eaten = true;
+    }
+    eaten
+}
+
+// Consumes everything up to and including `end`.
+fn take_until(chars: &mut Cursor, html: &mut String, end: &str) -> Option<()> {
+    while !html.ends_with(end) {
+        html.push(chars.next()?);
+    }
+    Some(())
+}
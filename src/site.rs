@@ -0,0 +1,246 @@
+// Static site builds: a directory of Markdown converted to a directory of
+// HTML pages, with everything else copied across.
+
+use crate::ast::{Node, NodeKind};
+use crate::page::render_page;
+use crate::{Diagnostic, Options, Page, split_and_parse};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Builds a static site: every `.md` file under `source` becomes an `.html`
+/// page under `output`, laid out by the [`Page`] template, and every other
+/// file is copied. The directory tree is mirrored, and relative links to
+/// `.md` files are pointed at their `.html` pages. Hidden files and
+/// directories, such as `.git`, are skipped.
+///
+/// Files whose output is newer than the source are left alone, so only what
+/// changed is rebuilt. After changing the template or options, use
+/// [`Site::force`] to rebuild everything. Output files whose source has been
+/// removed or renamed are listed in [`BuildReport::stale`], and deleted with
+/// [`Site::clean`]. With [`Options::legacy_02`] pages are rendered exactly
+/// as 0.2 did, so links are left as they are.
+///
+/// ```no_run
+/// let report = mark_html::Site::new("docs", "public")
+///     .page(mark_html::Page::new().stylesheet("/style.css"))
+///     .build()?;
+/// println!("{} pages built", report.converted.len());
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Site {
+    pub source: PathBuf,
+    pub output: PathBuf,
+    pub options: Options,
+    /// The layout of each page.
+    pub page: Page,
+    /// Rebuild every file, changed or not.
+    pub force: bool,
+    /// Delete the files and directories in the output that no source file
+    /// produced. Hidden ones, such as `.git`, are kept.
+    pub clean: bool,
+}
+
+/// What [`Site::build`] did. Paths are relative to the source directory.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BuildReport {
+    /// The Markdown files converted to pages.
+    pub converted: Vec<PathBuf>,
+    /// The other files copied.
+    pub copied: Vec<PathBuf>,
+    /// The number of files skipped as unchanged.
+    pub unchanged: usize,
+    /// The files and directories in the output that no source file
+    /// produced, such as the pages of Markdown files since removed or
+    /// renamed, relative to the output directory. They are deleted with
+    /// [`Site::clean`]; otherwise they are left alone, since the output may
+    /// hold files of its own.
+    pub stale: Vec<PathBuf>,
+    /// The problems [`parse_with_diagnostics`](crate::parse_with_diagnostics)
+    /// would report, for each converted file that has any.
+    pub diagnostics: Vec<(PathBuf, Vec<Diagnostic>)>,
+}
+
+impl Site {
+    pub fn new(source: impl Into<PathBuf>, output: impl Into<PathBuf>) -> Self {
+        Site {
+            source: source.into(),
+            output: output.into(),
+            options: Options::default(),
+            page: Page::default(),
+            force: false,
+            clean: false,
+        }
+    }
+
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn page(mut self, page: Page) -> Self {
+        self.page = page;
+        self
+    }
+
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    pub fn clean(mut self, clean: bool) -> Self {
+        self.clean = clean;
+        self
+    }
+
+    /// Builds the site. Errors name the file they are about.
+    pub fn build(&self) -> io::Result<BuildReport> {
+        let mut report = BuildReport::default();
+        fs::create_dir_all(&self.output).map_err(|error| with_path(error, &self.output))?;
+        // Don't build the output into itself when it is inside the source.
+        let output = self.output.canonicalize().ok();
+        self.build_dir(Path::new(""), output.as_deref(), &mut report)?;
+        Ok(report)
+    }
+
+    fn build_dir(
+        &self,
+        dir: &Path,
+        output: Option<&Path>,
+        report: &mut BuildReport,
+    ) -> io::Result<()> {
+        let source_dir = self.source.join(dir);
+        let mut entries = fs::read_dir(&source_dir)
+            .and_then(|entries| entries.collect::<io::Result<Vec<_>>>())
+            .map_err(|error| with_path(error, &source_dir))?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        // The names of the files in the output directory that come from
+        // this one.
+        let mut targets = Vec::new();
+        for entry in entries {
+            let name = entry.file_name();
+            if name.to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = dir.join(&name);
+            let source = self.source.join(&path);
+            if source.is_dir() {
+                if output.is_some() && source.canonicalize().ok().as_deref() == output {
+                    continue;
+                }
+                targets.push(name);
+                let target = self.output.join(&path);
+                fs::create_dir_all(&target).map_err(|error| with_path(error, &target))?;
+                self.build_dir(&path, output, report)?;
+            } else if path.extension().is_some_and(|extension| extension == "md") {
+                let target = self.output.join(path.with_extension("html"));
+                targets.extend(target.file_name().map(OsString::from));
+                if !self.force && is_fresh(&source, &target) {
+                    report.unchanged += 1;
+                    continue;
+                }
+                let diagnostics = self.convert(&source, &target)?;
+                if !diagnostics.is_empty() {
+                    report.diagnostics.push((path.clone(), diagnostics));
+                }
+                report.converted.push(path);
+            } else {
+                targets.push(name);
+                let target = self.output.join(&path);
+                if !self.force && is_fresh(&source, &target) {
+                    report.unchanged += 1;
+                    continue;
+                }
+                fs::copy(&source, &target).map_err(|error| with_path(error, &source))?;
+                report.copied.push(path);
+            }
+        }
+        self.find_stale(dir, &targets, report)
+    }
+
+    // Lists the entries of the output directory `dir` that aren't among
+    // `targets`, deleting them with `clean`. Hidden entries are left alone,
+    // and so is the source directory when it is inside the output.
+    fn find_stale(
+        &self,
+        dir: &Path,
+        targets: &[OsString],
+        report: &mut BuildReport,
+    ) -> io::Result<()> {
+        let output_dir = self.output.join(dir);
+        let mut entries = fs::read_dir(&output_dir)
+            .and_then(|entries| entries.collect::<io::Result<Vec<_>>>())
+            .map_err(|error| with_path(error, &output_dir))?;
+        entries.sort_by_key(|entry| entry.file_name());
+        let source = self
+            .source
+            .canonicalize()
+            .map_err(|error| with_path(error, &self.source))?;
+
+        for entry in entries {
+            let name = entry.file_name();
+            if name.to_string_lossy().starts_with('.') || targets.contains(&name) {
+                continue;
+            }
+            let target = entry.path();
+            if target
+                .canonicalize()
+                .is_ok_and(|target| source.starts_with(target))
+            {
+                continue;
+            }
+            if self.clean {
+                // Symbolic links are removed, not followed.
+                let removed = match entry.file_type()?.is_dir() {
+                    true => fs::remove_dir_all(&target),
+                    false => fs::remove_file(&target),
+                };
+                removed.map_err(|error| with_path(error, &target))?;
+            }
+            report.stale.push(dir.join(name));
+        }
+        Ok(())
+    }
+
+    fn convert(&self, source: &Path, target: &Path) -> io::Result<Vec<Diagnostic>> {
+        let input = fs::read_to_string(source).map_err(|error| with_path(error, source))?;
+        let (metadata, mut document, diagnostics) = split_and_parse(&input, &self.options);
+        rewrite_links(&mut document);
+        let html = render_page(&input, &metadata, &document, &self.options, &self.page);
+        fs::write(target, html).map_err(|error| with_path(error, target))?;
+        Ok(diagnostics)
+    }
+}
+
+// Whether `target` was written after `source` last changed.
+fn is_fresh(source: &Path, target: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+    match (modified(source), modified(target)) {
+        (Ok(source), Ok(target)) => target >= source,
+        _ => false,
+    }
+}
+
+// Points relative links to `.md` files, like `guide/setup.md#install`, at the
+// `.html` pages built from them. Links with a scheme, absolute paths and
+// fragment-only links are left alone.
+fn rewrite_links(node: &mut Node) {
+    if let NodeKind::Link { url, .. } = &mut node.kind {
+        let path_end = url.find(['?', '#']).unwrap_or(url.len());
+        let path = &url[..path_end];
+        let relative = !path.starts_with('/') && !path.contains(':');
+        if relative && let Some(stem) = path.strip_suffix(".md") {
+            *url = format!("{}.html{}", stem, &url[path_end..]);
+        }
+    }
+    if let Some(children) = node.children_mut() {
+        children.iter_mut().for_each(rewrite_links);
+    }
+}
+
+fn with_path(error: io::Error, path: &Path) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {}", path.display(), error))
+}
@@ -0,0 +1,39 @@
+// Heading slugs, generated the same way GitHub does so that links to
+// `#some-heading` keep working when documents move between the two.
+
+use std::collections::HashMap;
+
+// Lowercases `text`, drops everything but letters, digits, `-` and `_`, and
+// turns spaces into hyphens. Non-ASCII letters are kept as they are.
+pub(crate) fn slugify(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' | '_' => Some(c),
+            _ if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+// Hands out unique slugs for the headings of one document. Repeated headings
+// get `-1`, `-2`, ... appended, skipping any suffixed slug already taken.
+#[derive(Debug, Default)]
+pub(crate) struct Slugger {
+    occurrences: HashMap<String, usize>,
+}
+
+impl Slugger {
+    pub(crate) fn slug(&mut self, text: &str) -> String {
+        let original = slugify(text);
+        let mut slug = original.clone();
+        while self.occurrences.contains_key(&slug) {
+            let count = self.occurrences.entry(original.clone()).or_default();
+            *count += 1;
+            slug = format!("{}-{}", original, count);
+        }
+        self.occurrences.insert(slug.clone(), 0);
+        slug
+    }
+}
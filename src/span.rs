@@ -0,0 +1,137 @@
+// Source positions, and the cursor the lexer reads the input with, which keeps
+// track of them.
+
+/// A position in the input. Lines and columns start at 1; columns count bytes,
+/// like cmark's `data-sourcepos`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    /// Byte offset from the start of the input.
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    // The position `n` bytes further along the same line.
+    pub(crate) fn advance(self, n: usize) -> Position {
+        Position {
+            offset: self.offset + n,
+            line: self.line,
+            column: self.column + n,
+        }
+    }
+}
+
+/// The part of the input a token or node was parsed from, from `start` up to
+/// but not including `end`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    // The smallest span covering both `self` and `other`.
+    pub(crate) fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+}
+
+// Reads the input one character at a time, like `Peekable<Chars>`, while
+// keeping track of the current position. Cloning is cheap, so it can be used
+// for lookahead.
+#[derive(Debug, Clone)]
+pub(crate) struct Cursor<'a> {
+    input: &'a str,
+    offset: usize,
+    // Reading stops here, for lexing part of the input on its own.
+    end: usize,
+    line: usize,
+    line_start: usize,
+    current: Option<char>,
+}
+
+impl<'a> Cursor<'a> {
+    pub(crate) fn new(input: &'a str) -> Self {
+        Cursor::at(input, 0)
+    }
+
+    // A cursor over `input` that starts reading at byte `offset`.
+    pub(crate) fn at(input: &'a str, offset: usize) -> Self {
+        let before = &input[..offset];
+        let mut cursor = Cursor {
+            input,
+            offset,
+            end: input.len(),
+            line: before.matches('\n').count() + 1,
+            line_start: before.rfind('\n').map_or(0, |i| i + 1),
+            current: None,
+        };
+        cursor.current = cursor.rest().chars().next();
+        cursor
+    }
+
+    // A copy of this cursor that stops reading at byte `end`, or where it
+    // already stops if that is sooner.
+    pub(crate) fn until(&self, end: usize) -> Self {
+        let mut cursor = Cursor {
+            end: end.min(self.end),
+            ..self.clone()
+        };
+        cursor.current = cursor.rest().chars().next();
+        cursor
+    }
+
+    // A copy of this cursor moved back or forward to `position`, which must
+    // be a position in the same input.
+    pub(crate) fn seek(&self, position: Position) -> Self {
+        let mut cursor = Cursor {
+            offset: position.offset,
+            line: position.line,
+            line_start: position.offset + 1 - position.column,
+            ..self.clone()
+        };
+        cursor.current = cursor.rest().chars().next();
+        cursor
+    }
+
+    // Reads ahead up to byte `offset`.
+    pub(crate) fn skip_to(&mut self, offset: usize) {
+        while self.offset < offset && self.next().is_some() {}
+    }
+
+    pub(crate) fn peek(&self) -> Option<&char> {
+        self.current.as_ref()
+    }
+
+    // The unread part of the input.
+    pub(crate) fn rest(&self) -> &'a str {
+        &self.input[self.offset..self.end]
+    }
+
+    pub(crate) fn location(&self) -> Position {
+        Position {
+            offset: self.offset,
+            line: self.line,
+            column: self.offset - self.line_start + 1,
+        }
+    }
+}
+
+impl Iterator for Cursor<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.current?;
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.line_start = self.offset;
+        }
+        self.current = self.rest().chars().next();
+        Some(c)
+    }
+}
@@ -0,0 +1,532 @@
+// Terminal output: a document laid out for reading in a terminal, styled
+// with ANSI escape codes and wrapped to the terminal's width, for CLI help
+// text and release notes.
+
+use crate::ast::{Alignment, Document, Node, NodeKind};
+use crate::highlight::highlight_tokens;
+use crate::plain::{shows_url, strip_tags};
+use std::env;
+use std::io::{self, IsTerminal};
+
+/// What the terminal [`render_terminal`] writes for can show.
+///
+/// ```no_run
+/// let document = mark_html::parse_document("# Usage\n\nRun `tool --help`.");
+/// print!("{}", mark_html::render_terminal(&document, &mark_html::Terminal::detect()));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Terminal {
+    /// The number of columns text is wrapped to fit in.
+    pub width: usize,
+    /// Style text with ANSI escape codes: bold and italic text, colored
+    /// headings and highlighted code. Without it the output is plain text,
+    /// laid out the same way.
+    pub styled: bool,
+    /// Make links clickable with OSC 8 escape codes, which most terminals
+    /// understand. Only used with `styled`; otherwise, link destinations are
+    /// written in parentheses after the link text.
+    pub hyperlinks: bool,
+}
+
+impl Default for Terminal {
+    fn default() -> Self {
+        Terminal {
+            width: 80,
+            styled: true,
+            hyperlinks: true,
+        }
+    }
+}
+
+impl Terminal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The settings for standard output: styled if it is a terminal and
+    /// `NO_COLOR` isn't set, and as wide as `COLUMNS` says, or 80 columns.
+    pub fn detect() -> Self {
+        let styled = io::stdout().is_terminal()
+            && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty());
+        let width = env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.parse().ok())
+            .filter(|&columns| columns > 0)
+            .unwrap_or(80);
+        Terminal {
+            width,
+            styled,
+            hyperlinks: styled,
+        }
+    }
+
+    pub fn width(mut self, columns: usize) -> Self {
+        self.width = columns;
+        self
+    }
+
+    pub fn styled(mut self, enabled: bool) -> Self {
+        self.styled = enabled;
+        self
+    }
+
+    pub fn hyperlinks(mut self, enabled: bool) -> Self {
+        self.hyperlinks = enabled;
+        self
+    }
+}
+
+/// Lays a parsed document out for a terminal: headings in color, `•`
+/// bullets, code blocks indented and highlighted, and paragraphs wrapped to
+/// [`Terminal::width`]. Blocks are separated by blank lines, and every line
+/// ends with a newline, ready to be printed.
+pub fn render_terminal(document: &Document, terminal: &Terminal) -> String {
+    let renderer = TerminalRenderer { terminal };
+    let NodeKind::Document(blocks) = &document.kind else {
+        return renderer.render_block(document).join("");
+    };
+    blocks
+        .iter()
+        .map(|block| renderer.render_block(block).join(""))
+        .filter(|block| !block.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// How a piece of text is styled.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Attributes<'a> {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strikethrough: bool,
+    // An ANSI foreground color, 30 to 37 or 90 to 97.
+    color: Option<u8>,
+    link: Option<&'a str>,
+}
+
+// A run of text styled the same way throughout.
+type Run<'a> = (Attributes<'a>, String);
+
+const HEADING_COLORS: [u8; 3] = [35, 36, 34];
+const CODE_COLOR: u8 = 33;
+const LINK_COLOR: u8 = 34;
+const DIM_COLOR: u8 = 90;
+
+struct TerminalRenderer<'a> {
+    terminal: &'a Terminal,
+}
+
+impl<'a> TerminalRenderer<'a> {
+    // The lines of a block, each ending in a newline.
+    fn render_block(&self, node: &'a Node) -> Vec<String> {
+        match &node.kind {
+            NodeKind::Document(children) => children
+                .iter()
+                .flat_map(|child| self.render_block(child))
+                .collect(),
+            NodeKind::Heading(level, children) => {
+                let attributes = Attributes {
+                    bold: true,
+                    underline: *level == 1,
+                    color: Some(HEADING_COLORS[level.saturating_sub(1).min(2)]),
+                    ..Attributes::default()
+                };
+                let runs = self.runs(children, attributes);
+                let mut lines = self.wrap(&runs, "", "");
+                // Without styles, the first two levels are underlined the
+                // way setext headings are written.
+                if !self.terminal.styled && *level <= 2 {
+                    let width = lines.iter().map(|line| width(line)).max().unwrap_or(0);
+                    let rule = if *level == 1 { "=" } else { "-" };
+                    lines.push(rule.repeat(width));
+                }
+                finish(lines)
+            }
+            NodeKind::Paragraph(children) => {
+                finish(self.wrap(&self.runs(children, Attributes::default()), "", ""))
+            }
+            NodeKind::UnorderedList(children) => children
+                .iter()
+                .flat_map(|child| self.render_block(child))
+                .collect(),
+            NodeKind::OrderedList { start, children } => children
+                .iter()
+                .enumerate()
+                .flat_map(|(i, item)| match &item.kind {
+                    NodeKind::ListItem(children) => {
+                        self.render_item(children, &format!("{}.", start + i))
+                    }
+                    _ => self.render_block(item),
+                })
+                .collect(),
+            NodeKind::ListItem(children) => self.render_item(children, "•"),
+            NodeKind::CodeBlock { info, content } => {
+                let language = info.split_whitespace().next().unwrap_or_default();
+                finish(
+                    self.highlight(language, content)
+                        .into_iter()
+                        .map(|line| format!("    {}", line).trim_end().to_string())
+                        .collect(),
+                )
+            }
+            NodeKind::HtmlBlock(html) => finish(
+                strip_tags(html)
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(printable)
+                    .collect(),
+            ),
+            NodeKind::Table {
+                alignments,
+                children,
+            } => finish(self.render_table(alignments, children)),
+            NodeKind::FootnoteDefinition { label, children } => {
+                let runs = self.runs(children, Attributes::default());
+                let marker = self.paint(
+                    Attributes {
+                        color: Some(DIM_COLOR),
+                        ..Attributes::default()
+                    },
+                    &format!("[{}]", printable(label)),
+                );
+                let indent = " ".repeat(width(label) + 3);
+                finish(self.wrap(&runs, &format!("{} ", marker), &indent))
+            }
+            NodeKind::TableOfContents => Vec::new(),
+            _ => finish(self.wrap(
+                &self.runs(std::slice::from_ref(node), Attributes::default()),
+                "",
+                "",
+            )),
+        }
+    }
+
+    // A list item starting with `marker`, with the lines after the first
+    // indented to line up with its text.
+    fn render_item(&self, children: &'a [Node], marker: &str) -> Vec<String> {
+        let painted = self.paint(
+            Attributes {
+                color: Some(DIM_COLOR),
+                ..Attributes::default()
+            },
+            marker,
+        );
+        let runs = self.runs(children, Attributes::default());
+        let indent = " ".repeat(width(marker) + 1);
+        finish(self.wrap(&runs, &format!("{} ", painted), &indent))
+    }
+
+    // Tables aren't wrapped: their columns are padded to line up, with a
+    // rule under the header.
+    fn render_table(&self, alignments: &[Alignment], rows: &'a [Node]) -> Vec<String> {
+        let rows = rows
+            .iter()
+            .map(|row| match &row.kind {
+                NodeKind::TableRow(cells) => cells
+                    .iter()
+                    .map(|cell| match &cell.kind {
+                        NodeKind::TableCell(children) => self.runs(children, Attributes::default()),
+                        _ => Vec::new(),
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            })
+            .collect::<Vec<Vec<Vec<Run>>>>();
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let widths = (0..columns)
+            .map(|i| {
+                rows.iter()
+                    .filter_map(|row| row.get(i))
+                    .map(|runs| runs.iter().map(|(_, text)| width(text)).sum())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect::<Vec<usize>>();
+        let mut lines = Vec::new();
+        for (i, row) in rows.iter().enumerate() {
+            let cells = row.iter().enumerate().map(|(column, runs)| {
+                let runs = match i {
+                    0 => runs
+                        .iter()
+                        .map(|(attributes, text)| {
+                            let bold = Attributes {
+                                bold: true,
+                                ..*attributes
+                            };
+                            (bold, text.clone())
+                        })
+                        .collect(),
+                    _ => runs.clone(),
+                };
+                let text = runs.iter().map(|(_, text)| width(text)).sum::<usize>();
+                let padding = widths[column].saturating_sub(text);
+                let (left, right) = match alignments.get(column) {
+                    Some(Alignment::Right) => (padding, 0),
+                    Some(Alignment::Center) => (padding / 2, padding - padding / 2),
+                    _ => (0, padding),
+                };
+                let painted = runs
+                    .iter()
+                    .map(|(attributes, text)| self.paint(*attributes, text))
+                    .collect::<String>();
+                format!("{}{}{}", " ".repeat(left), painted, " ".repeat(right))
+            });
+            lines.push(
+                cells
+                    .collect::<Vec<String>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_string(),
+            );
+            if i == 0 {
+                let rule = widths
+                    .iter()
+                    .map(|&width| "─".repeat(width))
+                    .collect::<Vec<String>>()
+                    .join("  ");
+                lines.push(self.paint(
+                    Attributes {
+                        color: Some(DIM_COLOR),
+                        ..Attributes::default()
+                    },
+                    &rule,
+                ));
+            }
+        }
+        lines
+    }
+
+    // The lines of a code block, highlighted if the language is one the
+    // built-in highlighter knows.
+    fn highlight(&self, language: &str, code: &str) -> Vec<String> {
+        let tokens = match highlight_tokens(language, code) {
+            Some(tokens) if self.terminal.styled => tokens,
+            _ => vec![(None, code)],
+        };
+        let mut lines = vec![String::new()];
+        for (class, text) in tokens {
+            let attributes = Attributes {
+                italic: class == Some("comment"),
+                color: class.map(token_color),
+                ..Attributes::default()
+            };
+            for (i, part) in text.split('\n').enumerate() {
+                if i > 0 {
+                    lines.push(String::new());
+                }
+                if !part.is_empty() {
+                    let line = lines.last_mut().expect("there is always a line");
+                    line.push_str(&self.paint(attributes, &printable(part)));
+                }
+            }
+        }
+        if code.ends_with('\n') {
+            lines.pop();
+        }
+        lines
+    }
+
+    // The runs of text in inline nodes, styled with `attributes` and their
+    // own formatting.
+    fn runs(&self, nodes: &'a [Node], attributes: Attributes<'a>) -> Vec<Run<'a>> {
+        let mut runs = Vec::new();
+        for node in nodes {
+            match &node.kind {
+                NodeKind::Text(text) => runs.push((attributes, printable(text))),
+                NodeKind::Code(code) => {
+                    let code_attributes = Attributes {
+                        color: Some(CODE_COLOR),
+                        ..attributes
+                    };
+                    runs.push((code_attributes, printable(code)))
+                }
+                NodeKind::SoftBreak => runs.push((attributes, " ".to_string())),
+                NodeKind::Bold(children) => runs.extend(self.runs(
+                    children,
+                    Attributes {
+                        bold: true,
+                        ..attributes
+                    },
+                )),
+                NodeKind::Italic(children) => runs.extend(self.runs(
+                    children,
+                    Attributes {
+                        italic: true,
+                        ..attributes
+                    },
+                )),
+                NodeKind::Strikethrough(children) => runs.extend(self.runs(
+                    children,
+                    Attributes {
+                        strikethrough: true,
+                        ..attributes
+                    },
+                )),
+                NodeKind::Link { children, url, .. } => {
+                    let clickable = self.terminal.styled && self.terminal.hyperlinks;
+                    let link_attributes = Attributes {
+                        underline: true,
+                        color: Some(LINK_COLOR),
+                        link: Some(url.as_str()).filter(|_| clickable),
+                        ..attributes
+                    };
+                    let text = self.runs(children, link_attributes);
+                    let shown = shows_url(
+                        &text
+                            .iter()
+                            .map(|(_, text)| text.as_str())
+                            .collect::<String>(),
+                        url,
+                    );
+                    runs.extend(text);
+                    if !clickable && !shown {
+                        runs.push((attributes, format!(" ({})", printable(url))));
+                    }
+                }
+                NodeKind::TaskMarker(checked) => {
+                    runs.push((attributes, if *checked { "[x]" } else { "[ ]" }.to_string()))
+                }
+                NodeKind::FootnoteReference(label) => {
+                    let reference = Attributes {
+                        color: Some(DIM_COLOR),
+                        ..attributes
+                    };
+                    runs.push((reference, format!("[{}]", printable(label))))
+                }
+                NodeKind::HtmlInline(_) => {}
+                _ => {
+                    for line in self.render_block(node) {
+                        runs.push((attributes, line.trim_end().to_string()));
+                    }
+                }
+            }
+        }
+        runs
+    }
+
+    // Breaks runs into lines of at most `Terminal::width` columns, at spaces.
+    // The first line starts with `first`, the rest with `rest`, which should
+    // be as wide.
+    fn wrap(&self, runs: &[Run<'a>], first: &str, rest: &str) -> Vec<String> {
+        let words = words(runs);
+        let limit = self.terminal.width.saturating_sub(width(rest)).max(1);
+        let mut lines = Vec::new();
+        let mut line: Vec<&[Run]> = Vec::new();
+        let mut line_width = 0;
+        for word in &words {
+            let word_width = word.iter().map(|(_, text)| width(text)).sum::<usize>();
+            if !line.is_empty() && line_width + 1 + word_width > limit {
+                lines.push(std::mem::take(&mut line));
+                line_width = 0;
+            }
+            if !line.is_empty() {
+                line_width += 1;
+            }
+            line.push(word);
+            line_width += word_width;
+        }
+        if !line.is_empty() || lines.is_empty() {
+            lines.push(line);
+        }
+        lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let words = line
+                    .iter()
+                    .map(|word| {
+                        word.iter()
+                            .map(|(attributes, text)| self.paint(*attributes, text))
+                            .collect::<String>()
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" ");
+                format!("{}{}", if i == 0 { first } else { rest }, words)
+            })
+            .collect()
+    }
+
+    // `text` with the escape codes for `attributes` around it, if styled.
+    fn paint(&self, attributes: Attributes, text: &str) -> String {
+        if !self.terminal.styled {
+            return text.to_string();
+        }
+        let mut codes = Vec::new();
+        for (on, code) in [
+            (attributes.bold, 1),
+            (attributes.italic, 3),
+            (attributes.underline, 4),
+            (attributes.strikethrough, 9),
+        ] {
+            if on {
+                codes.push(code.to_string());
+            }
+        }
+        codes.extend(attributes.color.map(|color| color.to_string()));
+        let mut painted = match codes.is_empty() {
+            true => text.to_string(),
+            false => format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text),
+        };
+        if let Some(url) = attributes.link {
+            painted = format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", printable(url), painted);
+        }
+        painted
+    }
+}
+
+// Splits runs into words at spaces, each word being the runs it is made of.
+fn words<'a>(runs: &[Run<'a>]) -> Vec<Vec<Run<'a>>> {
+    let mut words = Vec::new();
+    let mut word: Vec<Run> = Vec::new();
+    for (attributes, text) in runs {
+        for (i, part) in text.split(' ').enumerate() {
+            if i > 0 && !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            if part.is_empty() {
+                continue;
+            }
+            match word.last_mut() {
+                Some((last, text)) if last == attributes => text.push_str(part),
+                _ => word.push((*attributes, part.to_string())),
+            }
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+// `text` without control characters, which would let the document write
+// escape codes of its own. Tabs are harmless and kept.
+fn printable(text: &str) -> String {
+    text.chars()
+        .filter(|&c| c == '\t' || !c.is_control())
+        .collect()
+}
+
+// Ends each line with a newline.
+fn finish(lines: Vec<String>) -> Vec<String> {
+    lines.into_iter().map(|line| line + "\n").collect()
+}
+
+// The color for a class of highlighted code, close to `HIGHLIGHT_CSS`.
+fn token_color(class: &str) -> u8 {
+    match class {
+        "keyword" | "meta" => 35,
+        "type" | "number" | "literal" => 33,
+        "function" => 34,
+        "string" | "inserted" => 32,
+        "variable" | "key" | "deleted" => 31,
+        "comment" => DIM_COLOR,
+        _ => 39,
+    }
+}
+
+// The number of columns `text` takes up, taking every character as one.
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
@@ -10,9 +10,19 @@ fn test_lex_heading() {
 fn test_lex_bold() {
     let input = "**bold text**";
     let expected = vec![
-        Token::BoldStart,
+        Token::Delimiter {
+            ch: '*',
+            count: 2,
+            can_open: true,
+            can_close: false,
+        },
         Token::Text("bold text".to_string()),
-        Token::BoldEnd,
+        Token::Delimiter {
+            ch: '*',
+            count: 2,
+            can_open: false,
+            can_close: true,
+        },
     ];
     assert_eq!(lex(input), expected);
 }
@@ -21,9 +31,19 @@ fn test_lex_bold() {
 fn test_lex_italic() {
     let input = "*italic text*";
     let expected = vec![
-        Token::ItalicStart,
+        Token::Delimiter {
+            ch: '*',
+            count: 1,
+            can_open: true,
+            can_close: false,
+        },
         Token::Text("italic text".to_string()),
-        Token::ItalicEnd,
+        Token::Delimiter {
+            ch: '*',
+            count: 1,
+            can_open: false,
+            can_close: true,
+        },
     ];
     assert_eq!(lex(input), expected);
 }
@@ -36,13 +56,33 @@ fn test_lex_mixed_and_multiline() {
         Token::Text("Header".to_string()),
         Token::NewLine,
         Token::Text("Hello ".to_string()),
-        Token::BoldStart,
+        Token::Delimiter {
+            ch: '*',
+            count: 2,
+            can_open: true,
+            can_close: false,
+        },
         Token::Text("world".to_string()),
-        Token::BoldEnd,
+        Token::Delimiter {
+            ch: '*',
+            count: 2,
+            can_open: false,
+            can_close: true,
+        },
         Token::Text(" in ".to_string()),
-        Token::ItalicStart,
+        Token::Delimiter {
+            ch: '*',
+            count: 1,
+            can_open: true,
+            can_close: false,
+        },
         Token::Text("Rust".to_string()),
-        Token::ItalicEnd,
+        Token::Delimiter {
+            ch: '*',
+            count: 1,
+            can_open: false,
+            can_close: true,
+        },
         Token::Text("!".to_string()),
     ];
     assert_eq!(lex(input), expected);
@@ -59,10 +99,10 @@ fn test_lex_no_space_after_heading() {
 #[test]
 fn test_parse_heading() {
     let tokens = vec![Token::Heading(1), Token::Text("Hello".to_string())];
-    let expected = Node::Document(vec![Node::Heading(
+    let expected = Node::new(NodeKind::Document(vec![Node::new(NodeKind::Heading(
         1,
-        vec![Node::Text("Hello".to_string())],
-    )]);
+        vec![Node::new(NodeKind::Text("Hello".to_string()))],
+    ))]));
     assert_eq!(parse(&tokens), expected);
 }
 
@@ -70,16 +110,30 @@ fn test_parse_heading() {
 fn test_parse_paragraph() {
     let tokens = vec![
         Token::Text("This is a ".to_string()),
-        Token::BoldStart,
+        Token::Delimiter {
+            ch: '*',
+            count: 2,
+            can_open: true,
+            can_close: false,
+        },
         Token::Text("test".to_string()),
-        Token::BoldEnd,
+        Token::Delimiter {
+            ch: '*',
+            count: 2,
+            can_open: false,
+            can_close: true,
+        },
         Token::Text(".".to_string()),
     ];
-    let expected = Node::Document(vec![Node::Paragraph(vec![
-        Node::Text("This is a ".to_string()),
-        Node::Bold(vec![Node::Text("test".to_string())]),
-        Node::Text(".".to_string()),
-    ])]);
+    let expected = Node::new(NodeKind::Document(vec![Node::new(NodeKind::Paragraph(
+        vec![
+            Node::new(NodeKind::Text("This is a ".to_string())),
+            Node::new(NodeKind::Bold(vec![Node::new(NodeKind::Text(
+                "test".to_string(),
+            ))])),
+            Node::new(NodeKind::Text(".".to_string())),
+        ],
+    ))]));
     assert_eq!(parse(&tokens), expected);
 }
 
@@ -91,72 +145,112 @@ fn test_parse_multiline() {
         Token::NewLine,
         Token::Text("Some text.".to_string()),
     ];
-    let expected = Node::Document(vec![
-        Node::Heading(2, vec![Node::Text("Title".to_string())]),
-        Node::Paragraph(vec![Node::Text("Some text.".to_string())]),
-    ]);
+    let expected = Node::new(NodeKind::Document(vec![
+        Node::new(NodeKind::Heading(
+            2,
+            vec![Node::new(NodeKind::Text("Title".to_string()))],
+        )),
+        Node::new(NodeKind::Paragraph(vec![Node::new(NodeKind::Text(
+            "Some text.".to_string(),
+        ))])),
+    ]));
     assert_eq!(parse(&tokens), expected);
 }
 
 #[test]
 fn test_parse_nested_styles() {
     let tokens = vec![
-        Token::BoldStart,
+        Token::Delimiter {
+            ch: '*',
+            count: 2,
+            can_open: true,
+            can_close: false,
+        },
         Token::Text("bold and ".to_string()),
-        Token::ItalicStart,
+        Token::Delimiter {
+            ch: '*',
+            count: 1,
+            can_open: true,
+            can_close: false,
+        },
         Token::Text("italic".to_string()),
-        Token::ItalicEnd,
-        Token::BoldEnd,
+        Token::Delimiter {
+            ch: '*',
+            count: 3,
+            can_open: false,
+            can_close: true,
+        },
     ];
-    let expected = Node::Document(vec![Node::Paragraph(vec![Node::Bold(vec![
-        Node::Text("bold and ".to_string()),
-        Node::Italic(vec![Node::Text("italic".to_string())]),
-    ])])]);
+    let expected = Node::new(NodeKind::Document(vec![Node::new(NodeKind::Paragraph(
+        vec![Node::new(NodeKind::Bold(vec![
+            Node::new(NodeKind::Text("bold and ".to_string())),
+            Node::new(NodeKind::Italic(vec![Node::new(NodeKind::Text(
+                "italic".to_string(),
+            ))])),
+        ]))],
+    ))]));
     assert_eq!(parse(&tokens), expected);
 }
 
 // Render tests
 #[test]
 fn test_render_heading() {
-    let node = Node::Heading(1, vec![Node::Text("Test".to_string())]);
+    let node = Node::new(NodeKind::Heading(
+        1,
+        vec![Node::new(NodeKind::Text("Test".to_string()))],
+    ));
     assert_eq!(render(&node), "<h1>Test</h1>");
 }
 
 #[test]
 fn test_render_paragraph() {
-    let node = Node::Paragraph(vec![
-        Node::Text("This is ".to_string()),
-        Node::Bold(vec![Node::Text("bold".to_string())]),
-        Node::Text(".".to_string()),
-    ]);
+    let node = Node::new(NodeKind::Paragraph(vec![
+        Node::new(NodeKind::Text("This is ".to_string())),
+        Node::new(NodeKind::Bold(vec![Node::new(NodeKind::Text(
+            "bold".to_string(),
+        ))])),
+        Node::new(NodeKind::Text(".".to_string())),
+    ]));
     assert_eq!(render(&node), "<p>This is <strong>bold</strong>.</p>");
 }
 
 #[test]
 fn test_render_document() {
-    let node = Node::Document(vec![
-        Node::Heading(1, vec![Node::Text("Title".to_string())]),
-        Node::Paragraph(vec![Node::Text("Content.".to_string())]),
-    ]);
+    let node = Node::new(NodeKind::Document(vec![
+        Node::new(NodeKind::Heading(
+            1,
+            vec![Node::new(NodeKind::Text("Title".to_string()))],
+        )),
+        Node::new(NodeKind::Paragraph(vec![Node::new(NodeKind::Text(
+            "Content.".to_string(),
+        ))])),
+    ]));
     assert_eq!(render(&node), "<h1>Title</h1>\n<p>Content.</p>");
 }
 
 #[test]
 fn test_render_text() {
-    let node = Node::Document(vec![Node::Text("Text Data".to_string())]);
+    let node = Node::new(NodeKind::Document(vec![Node::new(NodeKind::Text(
+        "Text Data".to_string(),
+    ))]));
     assert_eq!(render(&node), "Text Data");
 }
 
 #[test]
 fn test_render_all() {
-    let node = vec![Node::Document(vec![
-        Node::Heading(1, vec![Node::Text("Title".to_string())]),
-        Node::Paragraph(vec![
-            Node::Text("This is ".to_string()),
-            Node::Bold(vec![Node::Text("bold".to_string())]),
-            Node::Text(".".to_string()),
-        ]),
-    ])];
+    let node = vec![Node::new(NodeKind::Document(vec![
+        Node::new(NodeKind::Heading(
+            1,
+            vec![Node::new(NodeKind::Text("Title".to_string()))],
+        )),
+        Node::new(NodeKind::Paragraph(vec![
+            Node::new(NodeKind::Text("This is ".to_string())),
+            Node::new(NodeKind::Bold(vec![Node::new(NodeKind::Text(
+                "bold".to_string(),
+            ))])),
+            Node::new(NodeKind::Text(".".to_string())),
+        ])),
+    ]))];
 
     assert_eq!(
         render_all(&node),
@@ -166,10 +260,11 @@ fn test_render_all() {
 
 #[test]
 fn test_render_link() {
-    let node = Node::Document(vec![Node::Link {
-        text: "github".to_string(),
+    let node = Node::new(NodeKind::Document(vec![Node::new(NodeKind::Link {
+        children: vec![Node::new(NodeKind::Text("github".to_string()))],
         url: "https://github.com/Shivrajsoni".to_string(),
-    }]);
+        title: None,
+    })]));
     assert_eq!(
         render(&node),
         "<a href=\"https://github.com/Shivrajsoni\">github</a>",
@@ -189,6 +284,33 @@ fn test_lex_unordered_list() {
     assert_eq!(lex(input), expected);
 }
 
+#[test]
+fn test_lex_other_bullets() {
+    let input = "* item one\n+ item two\n*not an item*";
+    let expected = vec![
+        Token::ListItemStart,
+        Token::Text("item one".to_string()),
+        Token::NewLine,
+        Token::ListItemStart,
+        Token::Text("item two".to_string()),
+        Token::NewLine,
+        Token::Delimiter {
+            ch: '*',
+            count: 1,
+            can_open: true,
+            can_close: false,
+        },
+        Token::Text("not an item".to_string()),
+        Token::Delimiter {
+            ch: '*',
+            count: 1,
+            can_open: false,
+            can_close: true,
+        },
+    ];
+    assert_eq!(lex(input), expected);
+}
+
 #[test]
 fn test_parse_unordered_list() {
     let tokens = vec![
@@ -198,19 +320,31 @@ fn test_parse_unordered_list() {
         Token::ListItemStart,
         Token::Text("item two".to_string()),
     ];
-    let expected = Node::Document(vec![Node::UnorderedList(vec![
-        Node::ListItem(vec![Node::Text("item one".to_string())]),
-        Node::ListItem(vec![Node::Text("item two".to_string())]),
-    ])]);
+    let expected = Node::new(NodeKind::Document(vec![Node::new(
+        NodeKind::UnorderedList(vec![
+            Node::new(NodeKind::ListItem(vec![Node::new(NodeKind::Text(
+                "item one".to_string(),
+            ))])),
+            Node::new(NodeKind::ListItem(vec![Node::new(NodeKind::Text(
+                "item two".to_string(),
+            ))])),
+        ]),
+    )]));
     assert_eq!(parse(&tokens), expected);
 }
 
 #[test]
 fn test_render_unordered_list() {
-    let node = Node::Document(vec![Node::UnorderedList(vec![
-        Node::ListItem(vec![Node::Text("item one".to_string())]),
-        Node::ListItem(vec![Node::Text("item two".to_string())]),
-    ])]);
+    let node = Node::new(NodeKind::Document(vec![Node::new(
+        NodeKind::UnorderedList(vec![
+            Node::new(NodeKind::ListItem(vec![Node::new(NodeKind::Text(
+                "item one".to_string(),
+            ))])),
+            Node::new(NodeKind::ListItem(vec![Node::new(NodeKind::Text(
+                "item two".to_string(),
+            ))])),
+        ]),
+    )]));
     let expected = "<ul>\n<li>item one</li>\n<li>item two</li>\n</ul>";
     assert_eq!(render(&node), expected);
 }
@@ -223,10 +357,42 @@ fn test_full_process_unordered_list() {
     assert_eq!(html, expected_html);
 }
 
+#[test]
+fn test_lex_ordered_list() {
+    let input = "1. one\n2) two\n1234567890. not an item";
+    let expected = vec![
+        Token::OrderedItemStart(1),
+        Token::Text("one".to_string()),
+        Token::NewLine,
+        Token::OrderedItemStart(2),
+        Token::Text("two".to_string()),
+        Token::NewLine,
+        Token::Text("1234567890. not an item".to_string()),
+    ];
+    assert_eq!(lex(input), expected);
+}
+
+#[test]
+fn test_full_process_ordered_list() {
+    assert_eq!(
+        to_html("1. one\n1. two\n\n3) three\n- four"),
+        "<ol>\n<li>one</li>\n<li>two</li>\n</ol>\n<ol start=\"3\">\n<li>three</li>\n</ol>\n<ul>\n<li>four</li>\n</ul>"
+    );
+    // Only a list starting at 1 interrupts a paragraph.
+    assert_eq!(
+        to_html("In\n2024. we moved\n1. first"),
+        "<p>In\n2024. we moved</p>\n<ol>\n<li>first</li>\n</ol>"
+    );
+    assert_eq!(to_html("1.5 million"), "<p>1.5 million</p>");
+}
+
 #[test]
 fn test_lex_code_block() {
     let input = "```rust\nlet x = 5;\n```";
-    let expected = vec![Token::CodeBlock("let x = 5;\n".to_string())];
+    let expected = vec![Token::CodeBlock {
+        info: "rust".to_string(),
+        code: "let x = 5;\n".to_string(),
+    }];
     assert_eq!(lex(input), expected);
 }
 
@@ -235,26 +401,1405 @@ fn test_parse_code_block() {
     let tokens = vec![
         Token::Text("Here is some code:".to_string()),
         Token::NewLine,
-        Token::CodeBlock("let a = 1;".to_string()),
+        Token::CodeBlock {
+            info: String::new(),
+            code: "let a = 1;".to_string(),
+        },
     ];
-    let expected = Node::Document(vec![
-        Node::Paragraph(vec![Node::Text("Here is some code:".to_string())]),
-        Node::CodeBlock("let a = 1;".to_string()),
-    ]);
+    let expected = Node::new(NodeKind::Document(vec![
+        Node::new(NodeKind::Paragraph(vec![Node::new(NodeKind::Text(
+            "Here is some code:".to_string(),
+        ))])),
+        Node::new(NodeKind::CodeBlock {
+            info: String::new(),
+            content: "let a = 1;".to_string(),
+        }),
+    ]));
     assert_eq!(parse(&tokens), expected);
 }
 
 #[test]
 fn test_full_process_code_block() {
     let input = "# Code Example\n\nHere is a block:\n\n```rust\nfn example() -> bool {\n    true\n}\n```\n\nThat was it.";
-    let expected_html = "<h1>Code Example</h1>\n<p>Here is a block:</p>\n<pre><code>fn example() -> bool {\n    true\n}\n</code></pre>\n<p>That was it.</p>";
+    let expected_html = "<h1>Code Example</h1>\n<p>Here is a block:</p>\n<pre><code>fn example() -&gt; bool {\n    true\n}\n</code></pre>\n<p>That was it.</p>";
     let html = to_html(input);
     assert_eq!(html, expected_html);
 }
 
 #[test]
 fn test_render_text_escaping() {
-    let node = Node::Paragraph(vec![Node::Text("<script>".to_string())]);
+    let node = Node::new(NodeKind::Paragraph(vec![Node::new(NodeKind::Text(
+        "<script>".to_string(),
+    ))]));
     assert_eq!(render(&node), "<p>&lt;script&gt;</p>");
 }
 
+#[test]
+fn test_lex_intraword_underscore() {
+    let input = "snake_case";
+    let expected = vec![
+        Token::Text("snake".to_string()),
+        Token::Delimiter {
+            ch: '_',
+            count: 1,
+            can_open: false,
+            can_close: false,
+        },
+        Token::Text("case".to_string()),
+    ];
+    assert_eq!(lex(input), expected);
+}
+
+#[test]
+fn test_full_process_stray_asterisk() {
+    let input = "2 * 3 = 6\nand *this* is italic";
+    let expected_html = "<p>2 * 3 = 6\nand <em>this</em> is italic</p>";
+    assert_eq!(to_html(input), expected_html);
+}
+
+#[test]
+fn test_full_process_triple_delimiters() {
+    assert_eq!(to_html("***x***"), "<p><em><strong>x</strong></em></p>");
+    assert_eq!(
+        to_html("***bold** italic*"),
+        "<p><em><strong>bold</strong> italic</em></p>"
+    );
+}
+
+#[test]
+fn test_full_process_rule_of_three() {
+    assert_eq!(
+        to_html("*foo**bar**baz*"),
+        "<p><em>foo<strong>bar</strong>baz</em></p>"
+    );
+    assert_eq!(to_html("*foo**bar*"), "<p><em>foo**bar</em></p>");
+}
+
+#[test]
+fn test_full_process_unmatched_delimiters() {
+    assert_eq!(to_html("**not closed"), "<p>**not closed</p>");
+    assert_eq!(to_html("a ** b"), "<p>a ** b</p>");
+    assert_eq!(
+        to_html("__strong__ and snake_case"),
+        "<p><strong>strong</strong> and snake_case</p>"
+    );
+}
+
+#[test]
+fn test_lex_link_children() {
+    let input = "[`to_html`](https://docs.rs)";
+    let expected = vec![
+        Token::LinkStart,
+        Token::CodeSpan("to_html".to_string()),
+        Token::LinkEnd {
+            url: "https://docs.rs".to_string(),
+            title: None,
+        },
+    ];
+    assert_eq!(lex(input), expected);
+}
+
+#[test]
+fn test_lex_code_span() {
+    assert_eq!(
+        lex("`` a ` b ``"),
+        vec![Token::CodeSpan("a ` b".to_string())]
+    );
+    assert_eq!(
+        lex("`open"),
+        vec![
+            Token::Text("`".to_string()),
+            Token::Text("open".to_string())
+        ]
+    );
+}
+
+#[test]
+fn test_full_process_link_with_formatting() {
+    assert_eq!(
+        to_html("See [**bold** link](/a) and [`code`](/b)."),
+        "<p>See <a href=\"/a\"><strong>bold</strong> link</a> and <a href=\"/b\"><code>code</code></a>.</p>"
+    );
+}
+
+#[test]
+fn test_full_process_nested_links() {
+    assert_eq!(
+        to_html("[foo [bar](/inner)](/outer)"),
+        "<p>[foo <a href=\"/inner\">bar</a>](/outer)</p>"
+    );
+    assert_eq!(
+        to_html("[see [1]](/notes)"),
+        "<p><a href=\"/notes\">see [1]</a></p>"
+    );
+    assert_eq!(
+        to_html("[[*a [b](/1)*](/2)](/3)"),
+        "<p>[[<em>a <a href=\"/1\">b</a></em>](/2)](/3)</p>"
+    );
+}
+
+#[test]
+fn test_nested_links_are_linear() {
+    assert_renders_quickly(&("[".repeat(24) + "a" + &"](u)".repeat(24)));
+    assert_renders_quickly(&("[".repeat(5000) + "a" + &"](u)".repeat(5000)));
+}
+
+#[test]
+fn test_full_process_malformed_link() {
+    assert_eq!(to_html("[text](url"), "<p>[text](url</p>");
+    assert_eq!(to_html("[*text*]"), "<p>[<em>text</em>]</p>");
+    // Link text ends with its paragraph.
+    assert_eq!(to_html("[one\n\ntwo](/u)"), "<p>[one</p>\n<p>two](/u)</p>");
+}
+
+#[test]
+fn test_unmatched_brackets_are_linear() {
+    assert_renders_quickly(&"[".repeat(20000));
+    assert_renders_quickly(&"[a](".repeat(20000));
+    assert_renders_quickly(&"[a](<b".repeat(20000));
+    assert_renders_quickly(&"[^a".repeat(20000));
+    assert_renders_quickly(&"<".repeat(20000));
+}
+
+#[test]
+fn test_lex_link_title() {
+    let input = "[t](/url \"the title\")";
+    let expected = vec![
+        Token::LinkStart,
+        Token::Text("t".to_string()),
+        Token::LinkEnd {
+            url: "/url".to_string(),
+            title: Some("the title".to_string()),
+        },
+    ];
+    assert_eq!(lex(input), expected);
+}
+
+#[test]
+fn test_full_process_link_balanced_parentheses() {
+    assert_eq!(
+        to_html("[Rust](https://en.wikipedia.org/wiki/Rust_(programming_language))"),
+        "<p><a href=\"https://en.wikipedia.org/wiki/Rust_(programming_language)\">Rust</a></p>"
+    );
+}
+
+#[test]
+fn test_full_process_link_titles() {
+    assert_eq!(
+        to_html("[a](/x 'single') [b](/y (parens)) [c](/z \"say \\\"hi\\\"\")"),
+        "<p><a href=\"/x\" title=\"single\">a</a> <a href=\"/y\" title=\"parens\">b</a> <a href=\"/z\" title=\"say &quot;hi&quot;\">c</a></p>"
+    );
+}
+
+#[test]
+fn test_full_process_angle_bracket_destination() {
+    assert_eq!(
+        to_html("[doc](<my file.md> \"Doc\")"),
+        "<p><a href=\"my%20file.md\" title=\"Doc\">doc</a></p>"
+    );
+    assert_eq!(
+        to_html("[doc](<my file.md)"),
+        "<p>[doc](&lt;my file.md)</p>"
+    );
+}
+
+#[test]
+fn test_lex_html_block() {
+    let input = "<details>\n<summary>More</summary>\n\nText";
+    let expected = vec![
+        Token::HtmlBlock("<details>\n<summary>More</summary>".to_string()),
+        Token::NewLine,
+        Token::NewLine,
+        Token::Text("Text".to_string()),
+    ];
+    assert_eq!(lex(input), expected);
+}
+
+#[test]
+fn test_lex_inline_html() {
+    let input = "Press <kbd>Ctrl</kbd> < 2";
+    let expected = vec![
+        Token::Text("Press ".to_string()),
+        Token::HtmlInline("<kbd>".to_string()),
+        Token::Text("Ctrl".to_string()),
+        Token::HtmlInline("</kbd>".to_string()),
+        Token::Text(" ".to_string()),
+        Token::Text("<".to_string()),
+        Token::Text(" 2".to_string()),
+    ];
+    assert_eq!(lex(input), expected);
+}
+
+#[test]
+fn test_inline_html_ends_with_its_paragraph() {
+    assert_eq!(
+        to_html("Use <!-- to start a comment.\n\n# Install\n\nRun it.\n\n## Usage -->"),
+        "<p>Use &lt;!-- to start a comment.</p>\n<h1>Install</h1>\n<p>Run it.</p>\n<h2>Usage --&gt;</h2>"
+    );
+    assert_eq!(
+        to_html("Intro <span title=\"a\n\n# Heading\n\n\">x</span>"),
+        "<p>Intro &lt;span title=\"a</p>\n<h1>Heading</h1>\n<p>\"&gt;x&lt;/span&gt;</p>"
+    );
+    // A heading is one line, and a block start interrupts the paragraph.
+    assert_eq!(
+        to_html("# a <!--\nb --> c <!--\n- d -->"),
+        "<h1>a &lt;!--</h1>\n<p>b --&gt; c &lt;!--</p>\n<ul>\n<li>d --&gt;</li>\n</ul>"
+    );
+    assert_eq!(
+        to_html("a <span\n  title=\"x\">y</span>"),
+        "<p>a &lt;span\n  title=\"x\"&gt;y&lt;/span&gt;</p>"
+    );
+}
+
+#[test]
+fn test_full_process_raw_html_escaped_by_default() {
+    let input = "<div>\n*hi*\n</div>\n\nA <b class=\"x\">tag</b>";
+    assert_eq!(
+        to_html(input),
+        "<p>&lt;div&gt;\n*hi*\n&lt;/div&gt;</p>\n<p>A &lt;b class=\"x\"&gt;tag&lt;/b&gt;</p>"
+    );
+}
+
+#[test]
+fn test_full_process_raw_html_passthrough() {
+    let options = Options {
+        raw_html: true,
+        ..Options::default()
+    };
+    let input = "<!-- note -->\n<details>\n<summary>More</summary>\n</details>\n\nPress <kbd>Ctrl</kbd> + **C**";
+    assert_eq!(
+        to_html_with_options(input, &options),
+        "<!-- note -->\n<details>\n<summary>More</summary>\n</details>\n<p>Press <kbd>Ctrl</kbd> + <strong>C</strong></p>"
+    );
+}
+
+#[test]
+fn test_full_process_html_block_kinds() {
+    let options = Options {
+        raw_html: true,
+        ..Options::default()
+    };
+    assert_eq!(
+        to_html_with_options(
+            "<script>\nlet a = 1;\n\nlet b = 2;\n</script>\nafter",
+            &options
+        ),
+        "<script>\nlet a = 1;\n\nlet b = 2;\n</script>\n<p>after</p>"
+    );
+    // A lone inline tag can't interrupt a paragraph, but can start a block.
+    assert_eq!(
+        to_html_with_options("text\n<span>\n\n<span>\ntext", &options),
+        "<p>text\n<span></p>\n<span>\ntext"
+    );
+}
+
+#[test]
+fn test_lex_indented_code_block() {
+    let input = "    let x = 5;\n\n\tlet y = 6;\n\nText";
+    let expected = vec![
+        Token::CodeBlock {
+            info: String::new(),
+            code: "let x = 5;\n\nlet y = 6;\n".to_string(),
+        },
+        Token::NewLine,
+        Token::NewLine,
+        Token::Text("Text".to_string()),
+    ];
+    assert_eq!(lex(input), expected);
+}
+
+#[test]
+fn test_parse_paragraph_continuation() {
+    let tokens = vec![
+        Token::Text("one".to_string()),
+        Token::NewLine,
+        Token::Text("two".to_string()),
+        Token::NewLine,
+        Token::Heading(1),
+        Token::Text("Title".to_string()),
+    ];
+    let expected = Node::new(NodeKind::Document(vec![
+        Node::new(NodeKind::Paragraph(vec![
+            Node::new(NodeKind::Text("one".to_string())),
+            Node::new(NodeKind::SoftBreak),
+            Node::new(NodeKind::Text("two".to_string())),
+        ])),
+        Node::new(NodeKind::Heading(
+            1,
+            vec![Node::new(NodeKind::Text("Title".to_string()))],
+        )),
+    ]));
+    assert_eq!(parse(&tokens), expected);
+}
+
+#[test]
+fn test_full_process_indented_code_block() {
+    let input = "Example:\n\n    fn main() {\n        println!(\"<hi>\");\n\n    }\n\nDone.";
+    let expected_html = "<p>Example:</p>\n<pre><code>fn main() {\n    println!(\"&lt;hi&gt;\");\n\n}\n</code></pre>\n<p>Done.</p>";
+    assert_eq!(to_html(input), expected_html);
+}
+
+#[test]
+fn test_full_process_indented_code_does_not_interrupt_paragraph() {
+    let input = "Some text\n    still *the* paragraph";
+    let expected_html = "<p>Some text\nstill <em>the</em> paragraph</p>";
+    assert_eq!(to_html(input), expected_html);
+}
+
+#[test]
+fn test_full_process_block_markers_mid_line() {
+    assert_eq!(to_html("1 # 2 - 3"), "<p>1 # 2 - 3</p>");
+    assert_eq!(
+        to_html("  ## Indented heading\n  - item"),
+        "<h2>Indented heading</h2>\n<ul>\n<li>item</li>\n</ul>"
+    );
+}
+
+#[test]
+fn test_lex_tilde_fence() {
+    let input = "~~~\ncode\n~~~";
+    let expected = vec![Token::CodeBlock {
+        info: String::new(),
+        code: "code\n".to_string(),
+    }];
+    assert_eq!(lex(input), expected);
+}
+
+#[test]
+fn test_full_process_fence_containing_fences() {
+    let input = "````markdown\n```rust\nlet x = 1;\n```\n````\n\nafter";
+    let expected_html = "<pre><code>```rust\nlet x = 1;\n```\n</code></pre>\n<p>after</p>";
+    assert_eq!(to_html(input), expected_html);
+}
+
+#[test]
+fn test_full_process_fence_closes_only_at_line_start() {
+    let input = "~~~\nuse ``` or ~~ inline ~~~\n~~~~\nText with ```inline``` code";
+    let expected_html = "<pre><code>use ``` or ~~ inline ~~~\n</code></pre>\n<p>Text with <code>inline</code> code</p>";
+    assert_eq!(to_html(input), expected_html);
+}
+
+#[test]
+fn test_full_process_fence_indentation() {
+    let input = "  ```\n  indented\n    more\nnone\n  ```";
+    let expected_html = "<pre><code>indented\n  more\nnone\n</code></pre>";
+    assert_eq!(to_html(input), expected_html);
+}
+
+#[test]
+fn test_full_process_unterminated_fence() {
+    let input = "Text\n```\nstill code\n\n# not a heading";
+    let expected_html = "<p>Text</p>\n<pre><code>still code\n\n# not a heading\n</code></pre>";
+    assert_eq!(to_html(input), expected_html);
+}
+
+#[test]
+fn test_slugify() {
+    assert_eq!(slug::slugify("Hello, World!"), "hello-world");
+    assert_eq!(slug::slugify("Über `to_html` & more"), "über-to_html--more");
+    assert_eq!(slug::slugify("日本語 見出し"), "日本語-見出し");
+}
+
+#[test]
+fn test_slugger_deduplicates() {
+    let mut slugger = slug::Slugger::default();
+    assert_eq!(slugger.slug("Intro"), "intro");
+    assert_eq!(slugger.slug("Intro"), "intro-1");
+    assert_eq!(slugger.slug("Intro 1"), "intro-1-1");
+    assert_eq!(slugger.slug("Intro"), "intro-2");
+}
+
+#[test]
+fn test_full_process_heading_ids() {
+    let options = Options {
+        heading_ids: true,
+        ..Options::default()
+    };
+    let input = "# Getting *Started*\n## Usage\n## Usage";
+    let expected_html = "<h1 id=\"getting-started\">Getting <em>Started</em></h1>\n<h2 id=\"usage\">Usage</h2>\n<h2 id=\"usage-1\">Usage</h2>";
+    assert_eq!(to_html_with_options(input, &options), expected_html);
+    assert_eq!(to_html("# Plain"), "<h1>Plain</h1>");
+}
+
+#[test]
+fn test_full_process_heading_anchors() {
+    let options = Options {
+        heading_anchors: true,
+        ..Options::default()
+    };
+    assert_eq!(
+        to_html_with_options("## The `lex` step", &options),
+        "<h2 id=\"the-lex-step\"><a class=\"anchor\" href=\"#the-lex-step\" aria-hidden=\"true\">#</a>The <code>lex</code> step</h2>"
+    );
+}
+
+#[test]
+fn test_table_of_contents_nesting() {
+    let document =
+        parse_document("# Guide\n## Install\n### From source\n## Usage\n# FAQ\n## Usage");
+    let entry = |text: &str, level, slug: &str, children| TocEntry {
+        text: text.to_string(),
+        level,
+        slug: slug.to_string(),
+        children,
+    };
+    let expected = vec![
+        entry(
+            "Guide",
+            1,
+            "guide",
+            vec![
+                entry(
+                    "Install",
+                    2,
+                    "install",
+                    vec![entry("From source", 3, "from-source", vec![])],
+                ),
+                entry("Usage", 2, "usage", vec![]),
+            ],
+        ),
+        entry("FAQ", 1, "faq", vec![entry("Usage", 2, "usage-1", vec![])]),
+    ];
+    assert_eq!(table_of_contents(&document, 1, 6), expected);
+}
+
+#[test]
+fn test_table_of_contents_level_range() {
+    let document = parse_document("# Title\n## *One*\n### Deep\n## One");
+    let toc = table_of_contents(&document, 2, 2);
+    let slugs: Vec<&str> = toc.iter().map(|entry| entry.slug.as_str()).collect();
+    assert_eq!(slugs, ["one", "one-1"]);
+    assert_eq!(toc[0].text, "One");
+    assert!(toc[0].children.is_empty());
+}
+
+#[test]
+fn test_render_toc() {
+    let document = parse_document("# A & B\n## C");
+    assert_eq!(
+        render_toc(&table_of_contents(&document, 1, 6)),
+        "<ul>\n<li><a href=\"#a--b\">A &amp; B</a>\n<ul>\n<li><a href=\"#c\">C</a></li>\n</ul>\n</li>\n</ul>"
+    );
+    assert_eq!(render_toc(&[]), "");
+}
+
+#[test]
+fn test_full_process_toc_placeholder() {
+    let input = "[[toc]]\n\n# Intro\n## Details";
+    let expected_html = "<ul>\n<li><a href=\"#intro\">Intro</a>\n<ul>\n<li><a href=\"#details\">Details</a></li>\n</ul>\n</li>\n</ul>\n<h1 id=\"intro\">Intro</h1>\n<h2 id=\"details\">Details</h2>";
+    assert_eq!(to_html(input), expected_html);
+}
+
+#[test]
+fn test_front_matter_yaml() {
+    let input = "---\ntitle: \"Hello: World\"\ndate: 2024-05-01\ndraft: false\nweight: 3\nrating: 4.5 # out of 5\ntags: [rust, 'markdown']\nauthors:\n  - Ann\n  - Bob\n---\n# Post";
+    let (html, metadata) = to_html_with_metadata(input);
+    assert_eq!(html, "<h1>Post</h1>");
+    assert_eq!(metadata["title"].as_str(), Some("Hello: World"));
+    assert_eq!(metadata["date"], Value::String("2024-05-01".to_string()));
+    assert_eq!(metadata["draft"], Value::Boolean(false));
+    assert_eq!(metadata["weight"], Value::Integer(3));
+    assert_eq!(metadata["rating"], Value::Float(4.5));
+    assert_eq!(
+        metadata["tags"],
+        Value::List(vec![
+            Value::String("rust".to_string()),
+            Value::String("markdown".to_string()),
+        ])
+    );
+    assert_eq!(
+        metadata["authors"],
+        Value::List(vec![
+            Value::String("Ann".to_string()),
+            Value::String("Bob".to_string()),
+        ])
+    );
+}
+
+#[test]
+fn test_front_matter_toml() {
+    let input = "+++\ntitle = \"Release notes\"\ntags = [\"a\", \"b\"]\n\n[author]\nname = \"Ann\"\n+++\nBody";
+    let (html, metadata) = to_html_with_metadata(input);
+    assert_eq!(html, "<p>Body</p>");
+    assert_eq!(metadata["title"].as_str(), Some("Release notes"));
+    assert_eq!(metadata["author.name"].as_str(), Some("Ann"));
+    assert_eq!(metadata.len(), 3);
+}
+
+#[test]
+fn test_front_matter_not_rendered() {
+    assert_eq!(to_html("---\ntitle: x\n---\nText"), "<p>Text</p>");
+    // Without a closing delimiter, it is just part of the document.
+    let (html, metadata) = to_html_with_metadata("---\ntitle: x");
+    assert_eq!(html, "<p>---\ntitle: x</p>");
+    assert!(metadata.is_empty());
+}
+
+// Builds the span from `start` to `end`, each given as (offset, line, column).
+fn span(start: (usize, usize, usize), end: (usize, usize, usize)) -> Span {
+    let position = |(offset, line, column)| Position {
+        offset,
+        line,
+        column,
+    };
+    Span {
+        start: position(start),
+        end: position(end),
+    }
+}
+
+fn children(node: &Node) -> &[Node] {
+    match &node.kind {
+        NodeKind::Document(children)
+        | NodeKind::Heading(_, children)
+        | NodeKind::Paragraph(children)
+        | NodeKind::Bold(children)
+        | NodeKind::Italic(children)
+        | NodeKind::Link { children, .. }
+        | NodeKind::UnorderedList(children)
+        | NodeKind::ListItem(children) => children,
+        _ => &[],
+    }
+}
+
+#[test]
+fn test_block_spans() {
+    let document = parse_document("# Title\n\nOne\ntwo\n\n- a\n- b\n\n```\ncode\n```");
+    let blocks = children(&document);
+    assert_eq!(blocks.len(), 4);
+    assert_eq!(blocks[0].span, span((0, 1, 1), (7, 1, 8)));
+    assert_eq!(blocks[1].span, span((9, 3, 1), (16, 4, 4)));
+    assert_eq!(blocks[2].span, span((18, 6, 1), (25, 7, 4)));
+    assert_eq!(children(&blocks[2])[1].span, span((22, 7, 1), (25, 7, 4)));
+    assert_eq!(blocks[3].span, span((27, 9, 1), (39, 11, 4)));
+    assert_eq!(document.span, span((0, 1, 1), (39, 11, 4)));
+}
+
+#[test]
+fn test_inline_spans() {
+    let document = parse_document("a ***b*** [*c*](/u) `d`");
+    let inlines = children(&children(&document)[0]);
+    // `***b***` is an italic wrapped around a bold, each taking its delimiters.
+    let NodeKind::Italic(italic) = &inlines[1].kind else {
+        panic!("expected emphasis, got {:?}", inlines[1]);
+    };
+    assert_eq!(inlines[1].span, span((2, 1, 3), (9, 1, 10)));
+    assert_eq!(italic[0].span, span((3, 1, 4), (8, 1, 9)));
+    assert_eq!(children(&italic[0])[0].span, span((5, 1, 6), (6, 1, 7)));
+
+    assert!(matches!(inlines[3].kind, NodeKind::Link { .. }));
+    assert_eq!(inlines[3].span, span((10, 1, 11), (19, 1, 20)));
+    assert_eq!(
+        children(&inlines[3])[0].span,
+        span((11, 1, 12), (14, 1, 15))
+    );
+    assert_eq!(inlines[5].span, span((20, 1, 21), (23, 1, 24)));
+}
+
+#[test]
+fn test_spans_count_front_matter_and_unicode() {
+    let document = parse_document("---\ntitle: x\n---\nHé *x*");
+    let paragraph = &children(&document)[0];
+    assert_eq!(paragraph.span, span((17, 4, 1), (24, 4, 8)));
+    // Columns count bytes, and `é` takes two.
+    assert_eq!(children(paragraph)[1].span, span((21, 4, 5), (24, 4, 8)));
+}
+
+#[test]
+fn test_link_text_is_inline_only() {
+    assert_eq!(
+        to_html("[# not a heading](/u)"),
+        "<p><a href=\"/u\"># not a heading</a></p>"
+    );
+    assert_eq!(
+        lex("[- x](/u)"),
+        vec![
+            Token::LinkStart,
+            Token::Text("-".to_string()),
+            Token::Text(" x".to_string()),
+            Token::LinkEnd {
+                url: "/u".to_string(),
+                title: None,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_source_positions() {
+    let options = Options {
+        source_positions: true,
+        ..Options::default()
+    };
+    assert_eq!(
+        to_html_with_options("# Title\n\nSome *text*\n\n- a\n- b", &options),
+        "<h1 data-sourcepos=\"1:1-1:7\">Title</h1>\n<p data-sourcepos=\"3:1-3:11\">Some <em>text</em></p>\n<ul data-sourcepos=\"5:1-6:3\">\n<li data-sourcepos=\"5:1-5:3\">a</li>\n<li data-sourcepos=\"6:1-6:3\">b</li>\n</ul>"
+    );
+    assert_eq!(
+        to_html_with_options("```\ncode\n```\n\n    indented", &options),
+        "<pre data-sourcepos=\"1:1-3:3\"><code>code\n</code></pre>\n<pre data-sourcepos=\"5:1-5:12\"><code>indented\n</code></pre>"
+    );
+}
+
+#[test]
+fn test_source_positions_with_ids_and_front_matter() {
+    let options = Options {
+        source_positions: true,
+        heading_ids: true,
+        ..Options::default()
+    };
+    assert_eq!(
+        to_html_with_options("---\ntitle: x\n---\n## Intro\ntext", &options),
+        "<h2 data-sourcepos=\"4:1-4:8\" id=\"intro\">Intro</h2>\n<p data-sourcepos=\"5:1-5:4\">text</p>"
+    );
+    // Off by default.
+    assert_eq!(to_html("# Title"), "<h1>Title</h1>");
+}
+
+#[test]
+fn test_diagnostics_unterminated_fence() {
+    let (document, diagnostics) = parse_with_diagnostics("Intro\n\n```rust\nfn main() {}\n");
+    assert_eq!(document, parse_document("Intro\n\n```rust\nfn main() {}\n"));
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert_eq!(diagnostics[0].code, "unterminated-code-fence");
+    assert_eq!(diagnostics[0].span, span((7, 3, 1), (14, 3, 8)));
+}
+
+#[test]
+fn test_diagnostics_unclosed_link() {
+    let (_, diagnostics) = parse_with_diagnostics("See [text](url and more\nnext line");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Warning);
+    assert_eq!(diagnostics[0].code, "unclosed-link");
+    assert_eq!(diagnostics[0].span, span((4, 1, 5), (23, 1, 24)));
+    // Brackets that never promised a link are fine.
+    assert!(
+        parse_with_diagnostics("[x] done, [see [1]](/notes)")
+            .1
+            .is_empty()
+    );
+}
+
+// Renders `input`, failing if that takes long enough to suggest the time
+// grows faster than the input.
+fn assert_renders_quickly(input: &str) {
+    let start = std::time::Instant::now();
+    to_html_with_options(input, &Options::preset(Preset::Gfm));
+    let elapsed = start.elapsed();
+    assert!(
+        elapsed < std::time::Duration::from_secs(2),
+        "{} bytes took {:?}",
+        input.len(),
+        elapsed
+    );
+}
+
+#[test]
+fn test_emphasis_is_linear() {
+    assert_renders_quickly(&"a* ".repeat(20000));
+    assert_renders_quickly(&("_a ".repeat(10000) + &"b* ".repeat(10000)));
+    assert_renders_quickly(&"*a* ".repeat(20000));
+    assert_renders_quickly(&"~~a~ ".repeat(20000));
+}
+
+#[test]
+fn test_diagnostics_unclosed_emphasis() {
+    let (_, diagnostics) = parse_with_diagnostics("**bold* and *fine* and a * b\n\n_open");
+    let codes: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (d.code, d.span.start.line))
+        .collect();
+    assert_eq!(
+        codes,
+        vec![("unclosed-emphasis", 1), ("unclosed-emphasis", 3)]
+    );
+    assert_eq!(diagnostics[0].span, span((0, 1, 1), (1, 1, 2)));
+    assert_eq!(
+        diagnostics[1].to_string(),
+        "3:1: warning: `_` is never closed, so it shows up as text [unclosed-emphasis]"
+    );
+    assert!(parse_with_diagnostics("snake_case and 2 * 3").1.is_empty());
+}
+
+#[test]
+fn test_reference_links() {
+    let input = "See [the docs][Docs], [Docs][] and [docs].\n\n[docs]: https://docs.rs \"Docs.rs\"\n[other]: /unused";
+    let link = "<a href=\"https://docs.rs\" title=\"Docs.rs\">";
+    assert_eq!(
+        to_html(input),
+        format!(
+            "<p>See {}the docs</a>, {}Docs</a> and {}docs</a>.</p>",
+            link, link, link
+        )
+    );
+    // Labels match ignoring case and runs of whitespace, and the first
+    // definition of a label wins.
+    assert_eq!(
+        to_html("[*Big*  Label]\n\n[*big* label]: /one\n[*BIG* LABEL]: /two"),
+        "<p><a href=\"/one\"><em>Big</em>  Label</a></p>"
+    );
+}
+
+#[test]
+fn test_reference_links_unresolved() {
+    assert_eq!(to_html("[x] and [y][z]"), "<p>[x] and [y][z]</p>");
+    let (_, diagnostics) = parse_with_diagnostics("[x] and [y][z]");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, "unresolved-reference");
+    assert_eq!(diagnostics[0].span, span((8, 1, 9), (14, 1, 15)));
+    // A definition can't interrupt a paragraph.
+    assert_eq!(to_html("text\n[a]: /u"), "<p>text\n[a]: /u</p>");
+}
+
+#[test]
+fn test_try_to_html() {
+    let options = Options::default();
+    assert_eq!(
+        try_to_html("# Fine\n\n*ok*", &options),
+        Ok("<h1>Fine</h1>\n<p><em>ok</em></p>".to_string())
+    );
+
+    let error = try_to_html("Intro\n\nsome **bold", &options).unwrap_err();
+    assert_eq!((error.line, error.column), (3, 6));
+    assert_eq!(
+        error.to_string(),
+        "3:6: `**` is never closed, so it shows up as text"
+    );
+
+    let error = try_to_html("See [notes][n].\n\n~~~\ncode", &options).unwrap_err();
+    assert_eq!((error.line, error.column), (1, 5));
+    assert_eq!(error.diagnostics.len(), 2);
+    assert_eq!(error.diagnostics[1].code, "unterminated-code-fence");
+}
+
+#[test]
+fn test_options_off_by_default() {
+    let input = "~~x~~ <https://x.io> www.x.io [^1]\n\n| a |\n| - |\n\n- [ ] task\n\n[^1]: Note.";
+    assert_eq!(to_html_with_options(input, &Options::new()), to_html(input));
+    assert_eq!(
+        to_html("~~x~~ <https://x.io>"),
+        "<p>~~x~~ &lt;https://x.io&gt;</p>"
+    );
+}
+
+#[test]
+fn test_tables() {
+    let options = Options::new().tables(true);
+    assert_eq!(
+        to_html_with_options(
+            "| a | *b* | c |\n|:--|:-:|---:|\n| 1 | 2 \\| 3 |\n",
+            &options
+        ),
+        "<table>\n<thead>\n<tr>\n<th align=\"left\">a</th>\n<th align=\"center\"><em>b</em></th>\n<th align=\"right\">c</th>\n</tr>\n</thead>\n<tbody>\n<tr>\n<td align=\"left\">1</td>\n<td align=\"center\">2 | 3</td>\n<td align=\"right\"></td>\n</tr>\n</tbody>\n</table>"
+    );
+    // Without a delimiter row it is just a paragraph.
+    assert_eq!(to_html_with_options("| a |", &options), "<p>| a |</p>");
+    assert_eq!(
+        to_html_with_options("a | b\n- | -", &options),
+        "<table>\n<thead>\n<tr>\n<th>a</th>\n<th>b</th>\n</tr>\n</thead>\n</table>"
+    );
+}
+
+#[test]
+fn test_strikethrough_and_task_lists() {
+    let options = Options::new().strikethrough(true).task_lists(true);
+    assert_eq!(
+        to_html_with_options("~~gone~~ ~also~ ~~~not~~~", &options),
+        "<p><del>gone</del> <del>also</del> ~~~not~~~</p>"
+    );
+    assert_eq!(
+        to_html_with_options("- [ ] todo\n- [x] done", &options),
+        "<ul>\n<li><input type=\"checkbox\" disabled=\"\"> todo</li>\n<li><input type=\"checkbox\" checked=\"\" disabled=\"\"> done</li>\n</ul>"
+    );
+    assert_eq!(
+        to_html_with_options("- [x] done", &options.xhtml(true)),
+        "<ul>\n<li><input type=\"checkbox\" checked=\"\" disabled=\"\" /> done</li>\n</ul>"
+    );
+}
+
+#[test]
+fn test_autolinks() {
+    let options = Options::new().autolinks(true);
+    assert_eq!(
+        to_html_with_options("<https://x.io/a b> <me@x.io>", &options),
+        "<p>&lt;https://x.io/a b&gt; <a href=\"mailto:me@x.io\">me@x.io</a></p>"
+    );
+    let options = options.extended_autolinks(true);
+    assert_eq!(
+        to_html_with_options(
+            "See www.x.io/docs. Or https://x.io, or <https://y.io>.",
+            &options
+        ),
+        "<p>See <a href=\"http://www.x.io/docs\">www.x.io/docs</a>. Or <a href=\"https://x.io\">https://x.io</a>, or <a href=\"https://y.io\">https://y.io</a>.</p>"
+    );
+    // A bare URL in link text is just text, but `<...>` is a link of its own.
+    assert_eq!(
+        to_html_with_options("[www.x.io](/x) [<https://y.io>](/y)", &options),
+        "<p><a href=\"/x\">www.x.io</a> [<a href=\"https://y.io\">https://y.io</a>](/y)</p>"
+    );
+}
+
+#[test]
+fn test_footnotes() {
+    let options = Options::new().footnotes(true);
+    assert_eq!(
+        to_html_with_options(
+            "A[^b] and [^x], again[^B].\n\n[^b]: Note *b*.\n[^unused]: No.",
+            &options
+        ),
+        "<p>A<sup class=\"footnote-ref\"><a href=\"#fn-b\" id=\"fnref-b\" data-footnote-ref>1</a></sup> and [^x], again<sup class=\"footnote-ref\"><a href=\"#fn-b\" id=\"fnref-b-2\" data-footnote-ref>1</a></sup>.</p>\n<section class=\"footnotes\" data-footnotes>\n<ol>\n<li id=\"fn-b\">\n<p>Note <em>b</em>. <a href=\"#fnref-b\" class=\"footnote-backref\" data-footnote-backref aria-label=\"Back to content\">↩</a></p>\n</li>\n</ol>\n</section>"
+    );
+}
+
+#[test]
+fn test_filter_urls_and_soft_breaks() {
+    let options = Options::new().filter_urls(true);
+    assert_eq!(
+        to_html_with_options(
+            "[a](JavaScript:alert(1)) [b](data:text/html,x) [c](/ok)",
+            &options
+        ),
+        "<p><a href=\"\">a</a> <a href=\"\">b</a> <a href=\"/ok\">c</a></p>"
+    );
+    // Links can't show images, so image data gets no exception.
+    assert_eq!(
+        to_html_with_options(
+            "[d](data:image/svg+xml,<svg>) [e](DATA:image/png;base64,x)",
+            &options
+        ),
+        "<p><a href=\"\">d</a> <a href=\"\">e</a></p>"
+    );
+    let options = Options::new().soft_breaks(SoftBreak::Break);
+    assert_eq!(to_html_with_options("a\nb", &options), "<p>a<br>\nb</p>");
+    assert_eq!(
+        to_html_with_options("a\nb", &options.clone().xhtml(true)),
+        "<p>a<br />\nb</p>"
+    );
+    let options = options.soft_breaks(SoftBreak::Space);
+    assert_eq!(to_html_with_options("a\nb", &options), "<p>a b</p>");
+}
+
+#[test]
+fn test_preset_legacy_02() {
+    let options = Options::preset(Preset::Legacy02);
+    // Emphasis toggles on and off across the whole document, code blocks and
+    // link URLs aren't escaped, and links can't hold formatting.
+    let input = "*a\n\nb* [**x**](/a\"b)\n```rust\n<b>\n```";
+    let expected =
+        "<p><em>a</em></p>\n<p>b <a href=\"/a\"b\">**x**</a></p>\n<pre><code><b>\n</code></pre>";
+    assert_eq!(to_html_with_options(input, &options), expected);
+    assert_eq!(try_to_html(input, &options).unwrap(), expected);
+    assert_ne!(to_html(input), expected);
+    // Nothing added since 0.2 applies, not even front matter.
+    assert_eq!(
+        to_html_with_options(
+            "---\ntitle: x\n---\n~~x~~",
+            &options.clone().strikethrough(true)
+        ),
+        "<p>---</p>\n<p>title: x</p>\n<p>---</p>\n<p>~~x~~</p>"
+    );
+    assert_eq!(
+        parse_document_with_options("[a](b)", &options),
+        Node::new(NodeKind::Document(vec![Node::new(NodeKind::Paragraph(
+            vec![Node::new(NodeKind::Link {
+                children: vec![Node::new(NodeKind::Text("a".to_string()))],
+                url: "b".to_string(),
+                title: None,
+            })]
+        ))]))
+    );
+}
+
+#[test]
+fn test_presets() {
+    let input = "~~x~~ <https://x.io>\n\n| a |\n| - |";
+    assert_eq!(
+        to_html_with_options(input, &Options::preset(Preset::CommonMark)),
+        "<p>~~x~~ <a href=\"https://x.io\">https://x.io</a></p>\n<p>| a |\n| - |</p>"
+    );
+    assert_eq!(
+        to_html_with_options(input, &Options::preset(Preset::Gfm)),
+        "<p><del>x</del> <a href=\"https://x.io\">https://x.io</a></p>\n<table>\n<thead>\n<tr>\n<th>a</th>\n</tr>\n</thead>\n</table>"
+    );
+    // Presets are a starting point for the builder methods.
+    assert!(Options::preset(Preset::Gfm).heading_ids(true).tables);
+}
+
+#[test]
+fn test_to_html_page() {
+    let input = "Intro\n\n# First *heading*\n\n## Second";
+    assert_eq!(
+        to_html_page(input, &Options::new(), &Page::new()),
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>First heading</title>\n</head>\n<body>\n<p>Intro</p>\n<h1>First <em>heading</em></h1>\n<h2>Second</h2>\n</body>\n</html>\n"
+    );
+    let page = Page::new()
+        .description("A \"test\"")
+        .stylesheet("a.css?x=1&y=2")
+        .template("<nav>{{toc}}</nav><h1>{{title}}</h1>{{unknown}}");
+    let html = to_html_page(
+        "---\ntitle: A & B\n---\n# One",
+        &Options::new().xhtml(true),
+        &page,
+    );
+    assert!(html.contains("<meta charset=\"utf-8\" />"));
+    assert!(html.contains("<title>A &amp; B</title>"));
+    assert!(html.contains("<meta name=\"description\" content=\"A &quot;test&quot;\" />"));
+    assert!(html.contains("<link rel=\"stylesheet\" href=\"a.css?x=1&amp;y=2\" />"));
+    assert!(html.contains(
+        "<body>\n<nav><ul>\n<li><a href=\"#one\">One</a></li>\n</ul></nav><h1>A &amp; B</h1>{{unknown}}\n</body>"
+    ));
+}
+
+#[test]
+fn test_to_html_page_content() {
+    // The table of contents needs ids to link to, and placeholders in the
+    // document itself are left alone.
+    let page = Page::new().title("T").template("{{toc}}\n{{content}}");
+    let html = to_html_page(
+        "---\ndescription: D\n---\n# {{title}}",
+        &Options::new(),
+        &page,
+    );
+    assert!(html.contains("<title>T</title>\n<meta name=\"description\" content=\"D\">"));
+    assert!(html.contains("<h1 id=\"title\">{{title}}</h1>"));
+}
+
+// A fresh, empty directory for a test to write to.
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("mark-html-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_site_build() {
+    let source = temp_dir("site-source");
+    let output = temp_dir("site-output");
+    std::fs::create_dir_all(source.join("guide/.hidden")).unwrap();
+    std::fs::write(
+        source.join("index.md"),
+        "# Home\n\nSee [setup](guide/setup.md#install), [up](../x.md), [site](https://x.io/a.md) and [root](/a.md).",
+    )
+    .unwrap();
+    std::fs::write(source.join("guide/setup.md"), "**unclosed").unwrap();
+    std::fs::write(source.join("guide/logo.png"), [0u8, 1, 2]).unwrap();
+    std::fs::write(source.join("guide/.hidden/x.md"), "").unwrap();
+
+    let site = Site::new(&source, &output).page(Page::new().template("<main>{{content}}</main>"));
+    let report = site.build().unwrap();
+    assert_eq!(
+        report.converted,
+        vec![
+            std::path::PathBuf::from("guide/setup.md"),
+            std::path::PathBuf::from("index.md")
+        ]
+    );
+    assert_eq!(
+        report.copied,
+        vec![std::path::PathBuf::from("guide/logo.png")]
+    );
+    assert_eq!(report.diagnostics.len(), 1);
+    assert_eq!(report.diagnostics[0].1[0].code, "unclosed-emphasis");
+
+    let index = std::fs::read_to_string(output.join("index.html")).unwrap();
+    assert!(index.contains("<title>Home</title>"));
+    assert!(index.contains(
+        "<main><h1>Home</h1>\n<p>See <a href=\"guide/setup.html#install\">setup</a>, <a href=\"../x.html\">up</a>, <a href=\"https://x.io/a.md\">site</a> and <a href=\"/a.md\">root</a>.</p></main>"
+    ));
+    assert_eq!(
+        std::fs::read(output.join("guide/logo.png")).unwrap(),
+        [0, 1, 2]
+    );
+    assert!(!output.join("guide/.hidden").exists());
+
+    // Nothing changed, so nothing is rebuilt unless forced.
+    let report = site.build().unwrap();
+    assert_eq!((report.converted.len(), report.unchanged), (0, 3));
+    let report = site.clone().force(true).build().unwrap();
+    assert_eq!((report.converted.len(), report.copied.len()), (2, 1));
+
+    // Outputs whose source is gone are only deleted with `clean`.
+    std::fs::remove_file(source.join("guide/setup.md")).unwrap();
+    std::fs::remove_file(source.join("guide/logo.png")).unwrap();
+    std::fs::create_dir_all(output.join(".git")).unwrap();
+    std::fs::create_dir_all(output.join("old/dir")).unwrap();
+    let stale = vec![
+        std::path::PathBuf::from("guide/logo.png"),
+        std::path::PathBuf::from("guide/setup.html"),
+        std::path::PathBuf::from("old"),
+    ];
+    assert_eq!(site.build().unwrap().stale, stale);
+    assert!(output.join("guide/setup.html").exists());
+    assert_eq!(site.clone().clean(true).build().unwrap().stale, stale);
+    assert!(!output.join("guide/setup.html").exists());
+    assert!(!output.join("old").exists());
+    assert!(output.join(".git").exists() && output.join("index.html").exists());
+    assert_eq!(
+        site.clean(true).build().unwrap().stale,
+        Vec::<std::path::PathBuf>::new()
+    );
+
+    // A source directory inside the output is never stale.
+    let nested = Site::new(output.join("src"), &output).clean(true);
+    std::fs::create_dir_all(output.join("src")).unwrap();
+    std::fs::write(output.join("src/a.md"), "a").unwrap();
+    assert_eq!(
+        nested.build().unwrap().stale,
+        vec![
+            std::path::PathBuf::from("guide"),
+            std::path::PathBuf::from("index.html")
+        ]
+    );
+    assert!(output.join("src/a.md").exists() && output.join("a.html").exists());
+    std::fs::remove_dir_all(source).unwrap();
+    std::fs::remove_dir_all(output).unwrap();
+}
+
+#[test]
+fn test_code_block_info() {
+    let document = parse_document("```rust title=\"x\"\ncode\n```\n\n    indented");
+    let NodeKind::Document(blocks) = &document.kind else {
+        panic!("not a document");
+    };
+    assert!(
+        matches!(&blocks[0].kind, NodeKind::CodeBlock { info, .. } if info == "rust title=\"x\"")
+    );
+    assert!(matches!(&blocks[1].kind, NodeKind::CodeBlock { info, .. } if info.is_empty()));
+    // Without highlighting, the info string doesn't show up in the output.
+    assert_eq!(
+        to_html("```rust\nfn x\n```"),
+        "<pre><code>fn x\n</code></pre>"
+    );
+}
+
+#[test]
+fn test_syntax_highlighting() {
+    let options = Options::new().syntax_highlighting(true);
+    assert_eq!(
+        to_html_with_options("```rust\nlet s = \"<a>\"; // 1\n```", &options),
+        "<pre><code class=\"language-rust\"><span class=\"tok-keyword\">let</span> s = <span class=\"tok-string\">\"&lt;a&gt;\"</span>; <span class=\"tok-comment\">// 1</span>\n</code></pre>"
+    );
+    assert_eq!(
+        to_html_with_options("```toml\n[a]\nb = true\n```", &options),
+        "<pre><code class=\"language-toml\"><span class=\"tok-type\">[a]</span>\n<span class=\"tok-key\">b</span> = <span class=\"tok-literal\">true</span>\n</code></pre>"
+    );
+    // Unknown languages keep their class, and code without one is untouched.
+    assert_eq!(
+        to_html_with_options("```cobol\n<x>\n```\n\n```\nlet\n```", &options),
+        "<pre><code class=\"language-cobol\">&lt;x&gt;\n</code></pre>\n<pre><code>let\n</code></pre>"
+    );
+    assert!(
+        to_html_page("```js\nx\n```", &options, &Page::new())
+            .contains(&format!("<style>\n{}</style>", HIGHLIGHT_CSS))
+    );
+}
+
+#[test]
+fn test_custom_highlighter() {
+    struct Upper;
+    impl Highlighter for Upper {
+        fn highlight(&self, language: &str, code: &str) -> Option<String> {
+            match language {
+                "shout" => Some(code.to_uppercase()),
+                _ => BuiltinHighlighter.highlight(language, code),
+            }
+        }
+    }
+    assert_eq!(
+        to_html_with_highlighter(
+            "```shout\nhi\n```\n\n```diff\n+a\n```",
+            &Options::new(),
+            &Upper
+        ),
+        "<pre><code class=\"language-shout\">HI\n</code></pre>\n<pre><code class=\"language-diff\"><span class=\"tok-inserted\">+a</span>\n</code></pre>"
+    );
+}
+
+// Clears spans, so that trees parsed from different text can be compared.
+fn without_spans(mut node: Node) -> Node {
+    fn clear(node: &mut Node) {
+        node.span = Span::default();
+        if let Some(children) = node.children_mut() {
+            children.iter_mut().for_each(clear)
+        }
+    }
+    clear(&mut node);
+    node
+}
+
+#[test]
+fn test_render_markdown_round_trip() {
+    let options = Options::preset(Preset::Gfm);
+    let inputs = [
+        "# Title\n\nSome *em*, __strong__ and ***both*** text.",
+        "### Deep\nparagraph\nwith soft breaks",
+        "- one\n- two **bold** [link](http://x.com/a_(b) \"T \\\"q\\\"\")\n\n- other list",
+        "- [ ] todo\n- [x] done",
+        "`code` and ``a ` tick`` and `` `x` ``",
+        "```rust\nfn main() {}\n```\n\n    indented\n    code",
+        "````\n```\nnested\n```\n````",
+        "<div>\n<b>hi</b>\n</div>\n\ntext <em>inline</em>",
+        "| a | b |\n|:--|--:|\n| 1 \\| 2 | *x* |",
+        "~~gone~~ and *__mixed__* and **a *b* c**",
+        "Note[^1].\n\n[^1]: The note.",
+        "[ref][r] and <https://example.com>\n\n[r]: /path \"Title\"",
+        "[[toc]]\n\n## A\n\n## B",
+        "[empty]() and [spaced](<a b>)",
+        "para\n    # not a heading\n    - x\n    </div>\n    ```\n    1. y\n    | z\n    ~~~",
+        "*em\n    # inside* and\n    [^1]: not a note",
+        "Note[^1].\n\n[^1]: The note\n    # continued",
+        "text\n\n<!--\nnever closed",
+        "<?php\necho 1;\n\n",
+        "~<!--\n~~-->",
+        "` aa``a``",
+        "``` a` ``b `",
+    ];
+    for input in inputs {
+        let document = without_spans(parse_document_with_options(input, &options));
+        let markdown = render_markdown_with_options(&document, &options);
+        let reparsed = without_spans(parse_document_with_options(&markdown, &options));
+        assert_eq!(reparsed, document, "{:?} rendered as {:?}", input, markdown);
+        assert_eq!(render_markdown_with_options(&reparsed, &options), markdown);
+    }
+}
+
+#[test]
+fn test_render_markdown_canonical() {
+    let document = parse_document("# Title\n\n- a _b_\n- __c__\n\n~~~\ncode\n~~~\n\n    more");
+    assert_eq!(
+        render_markdown(&document),
+        "# Title\n\n- a *b*\n- **c**\n\n```\ncode\n```\n\n```\nmore\n```\n"
+    );
+    let gfm = Options::preset(Preset::Gfm);
+    let links = parse_document_with_options("<https://a.com> and www.b.com, too", &gfm);
+    assert_eq!(
+        render_markdown_with_options(&links, &gfm),
+        "<https://a.com> and www.b.com, too\n"
+    );
+    let commonmark = Options::preset(Preset::CommonMark);
+    let link = parse_document_with_options("[https://c.com](https://c.com)", &commonmark);
+    assert_eq!(
+        render_markdown_with_options(&link, &commonmark),
+        "<https://c.com>\n"
+    );
+    assert_eq!(render_markdown(&link), "[https://c.com](https://c.com)\n");
+}
+
+#[test]
+fn test_format_markdown() {
+    let input = "---\ntitle: Notes\n---\n##  Setup\n* one\n+ [two](a.md 'T')\n\n3) three\n1. four\n\n~~~sh\nmake\n~~~\n";
+    let expected = "---\ntitle: Notes\n---\n\n## Setup\n\n- one\n- [two](a.md \"T\")\n\n3. three\n4. four\n\n```sh\nmake\n```\n";
+    let formatted = format_markdown(input, &Options::new(), &Style::new());
+    assert_eq!(formatted, expected);
+    assert_eq!(
+        format_markdown(&formatted, &Options::new(), &Style::new()),
+        formatted
+    );
+    let stars = Style::new().bullet('*');
+    let starred = format_markdown("- a\n- b", &Options::new(), &stars);
+    assert_eq!(starred, "* a\n* b\n");
+    assert_eq!(format_markdown(&starred, &Options::new(), &stars), starred);
+    let tildes = Style::new().fence('~');
+    assert_eq!(
+        format_markdown("```\n~~~\n```", &Options::new(), &tildes),
+        "~~~~\n~~~\n~~~~\n"
+    );
+    assert_eq!(
+        format_markdown("```~~x\ncode\n```", &Options::new(), &tildes),
+        "~~~ ~~x\ncode\n~~~\n"
+    );
+    assert_eq!(
+        format_markdown("~~~a`b\nx\n~~~", &Options::new(), &Style::new()),
+        "~~~a`b\nx\n~~~\n"
+    );
+}
+
+#[test]
+fn test_format_markdown_after_list_items() {
+    // A list item is one line, so what follows starts a block of its own, as
+    // it does once `fmt` puts a blank line before it.
+    let options = Options::new().raw_html(true).tables(true);
+    for input in [
+        "- item\n[docs]: https://example.com\n\nSee [docs].",
+        "- a\n<b>x</b>",
+        "- a\n<div>",
+        "- a\n| x |\n| - |",
+        "- a\n    code",
+    ] {
+        let formatted = format_markdown(input, &options, &Style::new());
+        assert_eq!(
+            to_html_with_options(&formatted, &options),
+            to_html_with_options(input, &options),
+            "{:?}",
+            input
+        );
+    }
+    assert_eq!(
+        to_html("- item\n[docs]: https://example.com\n\nSee [docs]."),
+        "<ul>\n<li>item</li>\n</ul>\n<p>See <a href=\"https://example.com\">docs</a>.</p>"
+    );
+}
+
+#[test]
+fn test_format_tables() {
+    let options = Options::new().tables(true);
+    let input = "| a | long header | c |\n|:-|:-:|-:|\n| wide cell | x | \\| |";
+    assert_eq!(
+        format_markdown(input, &options, &Style::new()),
+        "| a         | long header |   c |\n| :-------- | :---------: | --: |\n| wide cell |      x      |  \\| |\n"
+    );
+    assert_eq!(
+        format_markdown(input, &options, &Style::new().align_tables(false)),
+        "| a | long header | c |\n| :-- | :-: | --: |\n| wide cell | x | \\| |\n"
+    );
+}
+
+#[test]
+fn test_format_wrap() {
+    let style = Style::new().wrap(16);
+    let input = "A paragraph\nwith `a code span` and - a dash, [a link](https://example.com/x).\n\n# A heading that is not wrapped";
+    let formatted = format_markdown(input, &Options::new(), &style);
+    assert_eq!(
+        formatted,
+        "A paragraph with\n`a code span`\nand - a dash, [a\nlink](https://example.com/x).\n\n# A heading that is not wrapped\n"
+    );
+    assert_eq!(
+        format_markdown(&formatted, &Options::new(), &style),
+        formatted
+    );
+    assert_eq!(
+        to_html(&formatted).replace('\n', " "),
+        to_html(input).replace('\n', " ")
+    );
+}
+
+#[test]
+fn test_to_plain_text() {
+    let input = "---\ntitle: T\n---\n# Title\n\nSome *em*, `code` and\n[a __link__](https://example.com \"t\").\n\n- one\n- two\n\n2. three\n\n```rust\nfn main() {\n\n}\n```\n\n<div>\n<b>Raw</b> <!-- c --> html\n</div>\n\n## Next";
+    assert_eq!(
+        to_plain_text(input),
+        "Title\n\nSome em, code and\na link.\n\n- one\n- two\n\n2. three\n\nfn main() {\n\n}\n\nRaw  html\n\nNext"
+    );
+}
+
+#[test]
+fn test_to_plain_text_with_options() {
+    let options = Options::preset(Preset::Gfm).link_urls(true);
+    let input = "[docs](/docs) <https://a.com> www.b.com <me@c.com>\n\n- [x] ~~done~~\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\nNote[^1]\n\n[^1]: Text.";
+    assert_eq!(
+        to_plain_text_with_options(input, &options),
+        "docs (/docs) https://a.com www.b.com me@c.com\n\n- [x] done\n\na\tb\n1\t2\n\nNote[1]\n\n[1] Text."
+    );
+    assert_eq!(
+        to_plain_text_with_options("[docs](/docs)", &Options::new()),
+        "docs"
+    );
+}
+
+#[test]
+fn test_render_terminal_plain() {
+    let input = "# Notes\n\nSome **bold** text and [a link](https://example.com) to wrap.\n\n- an item long enough to wrap\n\n```\ncode\n\n  indented\n```\n\n9. nine\n9. ten, long enough to wrap";
+    let terminal = Terminal::new().width(20).styled(false);
+    assert_eq!(
+        render_terminal(&parse_document(input), &terminal),
+        "Notes\n=====\n\nSome bold text and a\nlink\n(https://example.com)\nto wrap.\n\n• an item long\n  enough to wrap\n\n    code\n\n      indented\n\n9. nine\n10. ten, long enough\n    to wrap\n"
+    );
+}
+
+#[test]
+fn test_render_terminal_styled() {
+    let document =
+        parse_document("## Title\n\n*em* [link](https://example.com)\n\n```rust\nfn x() {}\n```");
+    assert_eq!(
+        render_terminal(&document, &Terminal::new()),
+        "\x1b[1;36mTitle\x1b[0m\n\n\x1b[3mem\x1b[0m \x1b]8;;https://example.com\x1b\\\x1b[4;34mlink\x1b[0m\x1b]8;;\x1b\\\n\n    \x1b[35mfn\x1b[0m \x1b[34mx\x1b[0m() {}\n"
+    );
+    let without_links = render_terminal(&document, &Terminal::new().hyperlinks(false));
+    assert!(without_links.contains("\x1b[4;34mlink\x1b[0m (https://example.com)"));
+}
+
+#[test]
+fn test_render_terminal_heading_level_zero() {
+    let text = Node::new(NodeKind::Text("x".to_string()));
+    let heading = Node::new(NodeKind::Heading(0, vec![text]));
+    assert_eq!(
+        render_terminal(&heading, &Terminal::new()),
+        "\x1b[1;35mx\x1b[0m\n"
+    );
+}
+
+#[test]
+fn test_render_terminal_control_characters() {
+    let input = "Hi \x1b[2J\x07there\u{9b}31m [x](<https://a.com/\x1b\\\x1b]8;;https://evil.com>)\n\n```\na\tb\x1b[1m\n```\n\n<div>\x1b[5m</div>";
+    let document = parse_document(input);
+    let styled = render_terminal(&document, &Terminal::new());
+    assert_eq!(
+        styled,
+        "Hi [2Jthere31m \x1b]8;;https://a.com/\\]8;;https://evil.com\x1b\\\x1b[4;34mx\x1b[0m\x1b]8;;\x1b\\\n\n    a\tb[1m\n\n[5m\n"
+    );
+    let plain = render_terminal(&document, &Terminal::new().styled(false));
+    assert!(!plain.contains(|c: char| c != '\n' && c != '\t' && c.is_control()));
+}
+
+#[test]
+fn test_render_latex() {
+    let input = "##  Costs & $ #1\n\n**Bold**, ~~no~~ and `a_b{}` for 10% ^_^ \\o/ 1 < 2 > 0\n\n- one\n- [x] two\n\n```\nraw \\ $ text\n```\n\n```python\nprint(1)\n```\n\n```rust\nfn x() {}\n```";
+    let document = parse_document_with_options(input, &Options::preset(Preset::Gfm));
+    assert_eq!(
+        render_latex(&document),
+        "\\subsection{Costs \\& \\$ \\#1}\n\n\\textbf{Bold}, \\sout{no} and \\texttt{a\\_b\\{\\}} for 10\\% \\textasciicircum{}\\_\\textasciicircum{} \\textbackslash{}o/ 1 \\textless{} 2 \\textgreater{} 0\n\n\\begin{itemize}\n  \\item one\n  \\item[{[x]}] two\n\\end{itemize}\n\n\\begin{verbatim}\nraw \\ $ text\n\\end{verbatim}\n\n\\begin{lstlisting}[language=Python]\nprint(1)\n\\end{lstlisting}\n\n\\begin{lstlisting}\nfn x() {}\n\\end{lstlisting}\n"
+    );
+}
+
+#[test]
+fn test_render_latex_tables_and_footnotes() {
+    let input = "| a | b |\n|:-:|--:|\n| 1 | 2 |\n\nSee[^n] and [^missing].\n\n[^n]: The *note*.";
+    let document = parse_document_with_options(input, &Options::preset(Preset::Gfm));
+    assert_eq!(
+        render_latex(&document),
+        "\\begin{tabular}{cr}\n\\hline\na & b \\\\\n\\hline\n1 & 2 \\\\\n\\hline\n\\end{tabular}\n\nSee\\footnote{The \\emph{note}.} and [\\textasciicircum{}missing].\n"
+    );
+    assert_eq!(render_latex(&parse_document("")), "");
+}
+
+#[test]
+fn test_render_latex_code_ending_its_environment() {
+    let document = parse_document(
+        "```\n\\end{verbatim}\n\\input{/etc/passwd}\n```\n\n```python\n  \\end{lstlisting}\n```",
+    );
+    assert_eq!(
+        render_latex(&document),
+        "\\texttt{\\textbackslash{}end\\{verbatim\\}}\\\\\n\\texttt{\\textbackslash{}input\\{/etc/passwd\\}}\n\n\\texttt{\\ \\ \\textbackslash{}end\\{lstlisting\\}}\n"
+    );
+}
+
+#[test]
+fn test_render_latex_items_starting_with_brackets() {
+    let document = parse_document("- [a link](x)\n- [draft] notes\n- plain");
+    assert_eq!(
+        render_latex(&document),
+        "\\begin{itemize}\n  \\item \\href{x}{a link}\n  \\item{} [draft] notes\n  \\item plain\n\\end{itemize}\n"
+    );
+}
+
+#[test]
+fn test_render_latex_ordered_lists() {
+    let document = parse_document("1. one\n2. two\n\n3) three");
+    assert_eq!(
+        render_latex(&document),
+        "\\begin{enumerate}\n  \\item one\n  \\item two\n\\end{enumerate}\n\n\\begin{enumerate}\n  \\setcounter{enumi}{2}\n  \\item three\n\\end{enumerate}\n"
+    );
+}
+
+#[test]
+fn test_render_latex_footnotes_citing_each_other() {
+    let input =
+        "See [^a] and [^Note].\n\n[^a]: A cites [^b].\n[^b]: B cites [^a].\n[^note]: Any case.";
+    let document = parse_document_with_options(input, &Options::preset(Preset::Gfm));
+    assert_eq!(
+        render_latex(&document),
+        "See \\footnote{A cites \\footnote{B cites [\\textasciicircum{}a].}.} and \\footnote{Any case.}.\n"
+    );
+}
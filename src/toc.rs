@@ -0,0 +1,97 @@
+// Table of contents generation. Slugs are handed out in document order by the
+// same `Slugger` as heading ids, so the links line up with the rendered ids.
+
+use crate::ast::{Document, Node, NodeKind, plain_text};
+use crate::html::{escape_attribute, escape_html};
+use crate::slug::Slugger;
+
+/// A heading in a table of contents, with the headings nested below it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TocEntry {
+    /// The heading text with formatting removed.
+    pub text: String,
+    /// The heading level, 1 for `#` through 6 for `######`.
+    pub level: usize,
+    /// The heading's `id`, as rendered with [`Options::heading_ids`](crate::Options::heading_ids).
+    pub slug: String,
+    pub children: Vec<TocEntry>,
+}
+
+/// Collects the headings of `document` with a level between `min_level` and
+/// `max_level` (inclusive) into a tree. A heading becomes a child of the
+/// closest preceding heading with a lower level.
+pub fn table_of_contents(document: &Document, min_level: usize, max_level: usize) -> Vec<TocEntry> {
+    let mut headings = Vec::new();
+    collect_headings(document, &mut headings);
+
+    // Every heading takes a slug, even the ones left out, to keep later
+    // duplicates numbered the same way as in the rendered document.
+    let mut slugger = Slugger::default();
+    let mut entries = Vec::new();
+    for (level, text) in headings {
+        let slug = slugger.slug(&text);
+        if (min_level..=max_level).contains(&level) {
+            insert_entry(
+                &mut entries,
+                TocEntry {
+                    text,
+                    level,
+                    slug,
+                    children: Vec::new(),
+                },
+            );
+        }
+    }
+    entries
+}
+
+/// Renders a table of contents as nested `<ul>` lists of links to the
+/// headings. Returns an empty string if there are no entries.
+pub fn render_toc(entries: &[TocEntry]) -> String {
+    if entries.is_empty() {
+        return String::new();
+    }
+    let items = entries
+        .iter()
+        .map(|entry| {
+            let nested = render_toc(&entry.children);
+            let nested = if nested.is_empty() {
+                nested
+            } else {
+                format!("\n{}\n", nested)
+            };
+            format!(
+                "<li><a href=\"#{}\">{}</a>{}</li>",
+                escape_attribute(&entry.slug),
+                escape_html(&entry.text),
+                nested
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    format!("<ul>\n{}\n</ul>", items)
+}
+
+fn collect_headings(node: &Node, headings: &mut Vec<(usize, String)>) {
+    match &node.kind {
+        NodeKind::Heading(level, children) => {
+            headings.push((*level, plain_text(children).trim().to_string()));
+        }
+        NodeKind::Document(children)
+        | NodeKind::UnorderedList(children)
+        | NodeKind::OrderedList { children, .. }
+        | NodeKind::ListItem(children) => {
+            for child in children {
+                collect_headings(child, headings);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn insert_entry(entries: &mut Vec<TocEntry>, entry: TocEntry) {
+    match entries.last_mut() {
+        Some(last) if last.level < entry.level => insert_entry(&mut last.children, entry),
+        _ => entries.push(entry),
+    }
+}